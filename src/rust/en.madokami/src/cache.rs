@@ -0,0 +1,102 @@
+use aidoku::std::{String, Vec};
+
+struct Entry<V> {
+    key: String,
+    value: V,
+    /// When the entry was stored, in seconds since the epoch.
+    stored_at: f64,
+}
+
+/// A small least-recently-used cache whose entries expire after `ttl` seconds.
+///
+/// Entries are kept in a `Vec` ordered from most to least recently used, which
+/// is plenty fast for the few dozen pages a session touches.
+pub struct LruCache<V> {
+    entries: Vec<Entry<V>>,
+    capacity: usize,
+    ttl: f64,
+}
+
+impl<V: Clone> LruCache<V> {
+    pub const fn new(capacity: usize, ttl: f64) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// Returns a fresh entry for `key` and marks it as recently used. Expired
+    /// entries are dropped.
+    pub fn get(&mut self, key: &str, now: f64) -> Option<V> {
+        let idx = self.entries.iter().position(|e| e.key == key)?;
+        if now - self.entries[idx].stored_at > self.ttl {
+            self.entries.remove(idx);
+            return None;
+        }
+        let entry = self.entries.remove(idx);
+        let value = entry.value.clone();
+        self.entries.insert(0, entry);
+        Some(value)
+    }
+
+    /// Stores `value` under `key`, evicting the least recently used entry if
+    /// the cache is full.
+    pub fn insert(&mut self, key: String, value: V, now: f64) {
+        self.entries.retain(|e| e.key != key);
+        self.entries.truncate(self.capacity.saturating_sub(1));
+        self.entries.insert(
+            0,
+            Entry {
+                key,
+                value,
+                stored_at: now,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(cache: &mut LruCache<u32>, key: &str, value: u32, now: f64) {
+        cache.insert(String::from(key), value, now);
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let mut cache = LruCache::new(4, 60.0);
+        insert(&mut cache, "a", 1, 0.0);
+        assert_eq!(cache.get("a", 60.0), Some(1));
+        assert_eq!(cache.get("a", 60.5), None);
+        // The expired entry is gone, not just hidden.
+        assert_eq!(cache.get("a", 0.0), None);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2, 60.0);
+        insert(&mut cache, "a", 1, 0.0);
+        insert(&mut cache, "b", 2, 0.0);
+        // Reading `a` makes `b` the least recently used.
+        assert_eq!(cache.get("a", 1.0), Some(1));
+        insert(&mut cache, "c", 3, 2.0);
+        assert_eq!(cache.get("b", 3.0), None);
+        assert_eq!(cache.get("a", 3.0), Some(1));
+        assert_eq!(cache.get("c", 3.0), Some(3));
+    }
+
+    #[test]
+    fn test_reinsert_moves_key_to_front() {
+        let mut cache = LruCache::new(2, 60.0);
+        insert(&mut cache, "a", 1, 0.0);
+        insert(&mut cache, "b", 2, 0.0);
+        insert(&mut cache, "a", 10, 1.0);
+        insert(&mut cache, "c", 3, 2.0);
+        assert_eq!(cache.get("a", 3.0), Some(10));
+        assert_eq!(cache.get("b", 3.0), None);
+        // Re-inserting also restarts the entry's TTL.
+        assert_eq!(cache.get("a", 61.0), Some(10));
+    }
+}
@@ -0,0 +1,20 @@
+use core::cell::RefCell;
+
+/// Source-wide mutable state that lives for the session.
+///
+/// Sources run on a single thread in wasm, so a `RefCell` is all that's
+/// needed; `with` keeps every borrow scoped to one call so they can't overlap.
+pub struct SessionCell<T>(RefCell<T>);
+
+// SAFETY: the wasm runtime never shares a source instance between threads.
+unsafe impl<T> Sync for SessionCell<T> {}
+
+impl<T> SessionCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self(RefCell::new(value))
+    }
+
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0.borrow_mut())
+    }
+}
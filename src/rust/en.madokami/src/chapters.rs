@@ -0,0 +1,392 @@
+use aidoku::{
+    std::{defaults::defaults_get, String, Vec},
+    Chapter,
+};
+use alloc::collections::BTreeMap;
+use core::cmp::Ordering;
+
+/// A chapter parsed from a series directory, along with the file metadata
+/// needed to pick between duplicate releases.
+pub struct ChapterFile {
+    pub chapter: Chapter,
+    pub size: u64,
+}
+
+impl ChapterFile {
+    fn has_chapter(&self) -> bool {
+        self.chapter.chapter >= 0.0
+    }
+
+    fn has_volume(&self) -> bool {
+        self.chapter.volume >= 0.0
+    }
+
+    /// A file that covers a whole volume rather than a single chapter.
+    fn is_volume_rip(&self) -> bool {
+        self.has_volume() && !self.has_chapter()
+    }
+
+    /// The volume a file belongs to. Releases in different languages never
+    /// cover the same content.
+    fn volume_key(&self) -> (String, f32) {
+        (self.chapter.lang.clone(), self.chapter.volume)
+    }
+}
+
+/// Which kind of release is kept when volume rips and chapter releases
+/// overlap.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReleasePreference {
+    Volumes,
+    Chapters,
+}
+
+pub struct DedupePolicy {
+    pub preference: ReleasePreference,
+    pub preferred_group: String,
+}
+
+impl DedupePolicy {
+    /// Reads the deduplication settings, returning `None` if it's disabled.
+    pub fn from_settings() -> Option<Self> {
+        let enabled = defaults_get("dedupeChapters")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        let preference = match defaults_get("preferredRelease")
+            .and_then(|v| v.as_string())
+            .map(|s| s.read())
+            .unwrap_or_default()
+            .as_str()
+        {
+            "chapters" => ReleasePreference::Chapters,
+            _ => ReleasePreference::Volumes,
+        };
+        let preferred_group = defaults_get("preferredGroup")
+            .and_then(|v| v.as_string())
+            .map(|s| s.read().trim().to_lowercase())
+            .unwrap_or_default();
+        Some(Self {
+            preference,
+            preferred_group,
+        })
+    }
+
+    fn matches_group(&self, file: &ChapterFile) -> bool {
+        !self.preferred_group.is_empty()
+            && file
+                .chapter
+                .scanlator
+                .to_lowercase()
+                .contains(&self.preferred_group)
+    }
+
+    /// Whether `candidate` should replace `current` as the release shown for
+    /// the same chapter: the preferred group wins, then the largest file.
+    fn prefers(&self, candidate: &ChapterFile, current: &ChapterFile) -> bool {
+        let (candidate_match, current_match) =
+            (self.matches_group(candidate), self.matches_group(current));
+        if candidate_match != current_match {
+            return candidate_match;
+        }
+        candidate.size > current.size
+    }
+}
+
+/// Hides files covering the same content, keeping one release per chapter
+/// according to `policy`.
+///
+/// Files with the same language, volume and chapter number are collapsed
+/// first. Volume rips are then matched against chapter releases in the same
+/// language that carry the same volume number; chapter releases without a
+/// volume can't be matched and are always kept, as are files without any
+/// numbering.
+pub fn dedupe(files: Vec<ChapterFile>, policy: &DedupePolicy) -> Vec<ChapterFile> {
+    let mut kept: Vec<ChapterFile> = Vec::with_capacity(files.len());
+    let mut slots: BTreeMap<(String, u32, u32), usize> = BTreeMap::new();
+    for file in files {
+        if !file.has_chapter() && !file.has_volume() {
+            kept.push(file);
+            continue;
+        }
+        let key = (
+            file.chapter.lang.clone(),
+            file.chapter.volume.to_bits(),
+            file.chapter.chapter.to_bits(),
+        );
+        match slots.get(&key) {
+            Some(&idx) => {
+                if policy.prefers(&file, &kept[idx]) {
+                    kept[idx] = file;
+                }
+            }
+            None => {
+                slots.insert(key, kept.len());
+                kept.push(file);
+            }
+        }
+    }
+
+    let ripped_volumes: Vec<(String, f32)> = kept
+        .iter()
+        .filter(|f| f.is_volume_rip())
+        .map(|f| f.volume_key())
+        .collect();
+    let chaptered_volumes: Vec<(String, f32)> = kept
+        .iter()
+        .filter(|f| f.has_chapter() && f.has_volume())
+        .map(|f| f.volume_key())
+        .collect();
+    kept.retain(|f| match policy.preference {
+        ReleasePreference::Volumes => {
+            !(f.has_chapter() && f.has_volume() && ripped_volumes.contains(&f.volume_key()))
+        }
+        ReleasePreference::Chapters => {
+            !(f.is_volume_rip() && chaptered_volumes.contains(&f.volume_key()))
+        }
+    });
+    kept
+}
+
+/// Sort bucket for a chapter: chapter releases without a volume (which usually
+/// continue past the last collected volume) come first, then anything with a
+/// volume, then unnumbered files.
+fn rank(volume: f32, chapter: f32) -> u8 {
+    match (volume >= 0.0, chapter >= 0.0) {
+        (false, true) => 2,
+        (true, _) => 1,
+        (false, false) => 0,
+    }
+}
+
+fn compare(a: (f32, f32, &str), b: (f32, f32, &str)) -> Ordering {
+    rank(b.0, b.1)
+        .cmp(&rank(a.0, a.1))
+        .then_with(|| b.0.total_cmp(&a.0))
+        .then_with(|| b.1.total_cmp(&a.1))
+        .then_with(|| a.2.to_lowercase().cmp(&b.2.to_lowercase()))
+}
+
+/// Sorts items into descending display order by the `(volume, chapter, title)`
+/// returned from `key`, where a negative volume or chapter means it's unknown.
+///
+/// Chapters of a volume are listed above that volume's rip, and unnumbered
+/// files end up at the bottom in alphabetical order. The sort is stable, so
+/// files that compare equal keep their directory order.
+pub fn sort_by_number<T, F>(items: &mut [T], key: F)
+where
+    F: Fn(&T) -> (f32, f32, &str),
+{
+    items.sort_by(|a, b| compare(key(a), key(b)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::parse_chapter_info;
+    use aidoku::std::String;
+
+    /// A file named `id`, where a negative volume or chapter means it's
+    /// unknown.
+    fn file(id: &str, volume: f32, chapter: f32, group: &str, size: u64) -> ChapterFile {
+        ChapterFile {
+            chapter: Chapter {
+                id: String::from(id),
+                volume,
+                chapter,
+                scanlator: String::from(group),
+                ..Default::default()
+            },
+            size,
+        }
+    }
+
+    fn in_lang(mut file: ChapterFile, lang: &str) -> ChapterFile {
+        file.chapter.lang = String::from(lang);
+        file
+    }
+
+    fn policy(preference: ReleasePreference, preferred_group: &str) -> DedupePolicy {
+        DedupePolicy {
+            preference,
+            preferred_group: String::from(preferred_group),
+        }
+    }
+
+    fn kept(files: Vec<ChapterFile>, policy: &DedupePolicy) -> Vec<String> {
+        dedupe(files, policy)
+            .into_iter()
+            .map(|file| file.chapter.id)
+            .collect()
+    }
+
+    /// Parses and sorts `filenames` the same way `get_chapter_list` does,
+    /// returning them in display (descending) order.
+    fn sorted(filenames: &[&'static str], manga_title: &str) -> Vec<&'static str> {
+        let mut items: Vec<(f32, f32, &str)> = filenames
+            .iter()
+            .map(|name| {
+                let info = parse_chapter_info(name, manga_title);
+                let chapter = if info.chapter > 0.0 { info.chapter } else { -1.0 };
+                let volume = if info.volume > 0.0 { info.volume } else { -1.0 };
+                (volume, chapter, *name)
+            })
+            .collect();
+        sort_by_number(&mut items, |item| *item);
+        items.into_iter().map(|item| item.2).collect()
+    }
+
+    #[test]
+    fn test_sort_volumes_then_chapters() {
+        assert_eq!(
+            sorted(
+                &[
+                    "Berserk - c351.zip",
+                    "Berserk v02 (2004) (Digital) (danke-Empire).cbz",
+                    "Berserk Extra.zip",
+                    "Berserk v01 (2003) (Digital) (danke-Empire).cbz",
+                    "Artbook.zip",
+                    "Berserk - c350 [Group].zip",
+                ],
+                "Berserk"
+            ),
+            [
+                "Berserk - c351.zip",
+                "Berserk - c350 [Group].zip",
+                "Berserk v02 (2004) (Digital) (danke-Empire).cbz",
+                "Berserk v01 (2003) (Digital) (danke-Empire).cbz",
+                "Artbook.zip",
+                "Berserk Extra.zip",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_numeric_not_lexicographic() {
+        assert_eq!(
+            sorted(
+                &["Gantz v10.cbz", "Gantz v9.cbz", "Gantz v100.cbz", "Gantz v1.cbz"],
+                "Gantz"
+            ),
+            ["Gantz v100.cbz", "Gantz v10.cbz", "Gantz v9.cbz", "Gantz v1.cbz"]
+        );
+    }
+
+    #[test]
+    fn test_sort_chapters_within_volume() {
+        assert_eq!(
+            sorted(
+                &[
+                    "Blame! v02 - c012.zip",
+                    "Blame! v01 - c002.zip",
+                    "Blame! v01.zip",
+                    "Blame! v01 - c001.zip",
+                ],
+                "Blame!"
+            ),
+            [
+                "Blame! v02 - c012.zip",
+                "Blame! v01 - c002.zip",
+                "Blame! v01 - c001.zip",
+                "Blame! v01.zip",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_unnumbered_is_stable() {
+        assert_eq!(
+            sorted(&["Omake.zip", "omake.zip", "Cover.jpg"], "Series"),
+            ["Cover.jpg", "Omake.zip", "omake.zip"]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_keeps_largest_release() {
+        let files = Vec::from([
+            file("small", 1.0, 3.0, "", 10),
+            file("large", 1.0, 3.0, "", 30),
+            file("medium", 1.0, 3.0, "", 20),
+            file("other", 1.0, 4.0, "", 5),
+        ]);
+        assert_eq!(
+            kept(files, &policy(ReleasePreference::Chapters, "")),
+            ["large", "other"]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_prefers_group_over_size() {
+        let files = Vec::from([
+            file("large", -1.0, 7.0, "Others", 50),
+            file("preferred", -1.0, 7.0, "Best Scans", 10),
+            file("preferred larger", -1.0, 7.0, "best scans", 20),
+        ]);
+        assert_eq!(
+            kept(files, &policy(ReleasePreference::Chapters, "best scans")),
+            ["preferred larger"]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_prefers_volumes() {
+        let files = Vec::from([
+            file("c001", 1.0, 1.0, "", 10),
+            file("c002", 1.0, 2.0, "", 10),
+            file("v01", 1.0, -1.0, "", 100),
+            file("v02 c003", 2.0, 3.0, "", 10),
+        ]);
+        assert_eq!(
+            kept(files, &policy(ReleasePreference::Volumes, "")),
+            ["v01", "v02 c003"]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_prefers_chapters() {
+        let files = Vec::from([
+            file("c001", 1.0, 1.0, "", 10),
+            file("v01", 1.0, -1.0, "", 100),
+            file("v02", 2.0, -1.0, "", 100),
+        ]);
+        assert_eq!(
+            kept(files, &policy(ReleasePreference::Chapters, "")),
+            ["c001", "v02"]
+        );
+    }
+
+    #[test]
+    fn test_dedupe_keeps_chapters_without_volume() {
+        // There's no way to tell which volume these chapters belong to, so
+        // they're shown alongside the volume rip, as is the unnumbered file.
+        for preference in [ReleasePreference::Volumes, ReleasePreference::Chapters] {
+            assert_eq!(
+                kept(
+                    Vec::from([
+                        file("v01", 1.0, -1.0, "", 100),
+                        file("c001", -1.0, 1.0, "", 10),
+                        file("c002", -1.0, 2.0, "", 10),
+                        file("extras", -1.0, -1.0, "", 10),
+                    ]),
+                    &policy(preference, "")
+                ),
+                ["v01", "c001", "c002", "extras"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_dedupe_keeps_each_language() {
+        let files = Vec::from([
+            in_lang(file("en c005", 1.0, 5.0, "", 10), "en"),
+            in_lang(file("raw c005", 1.0, 5.0, "", 50), "ja"),
+            in_lang(file("raw v01", 1.0, -1.0, "", 100), "ja"),
+        ]);
+        assert_eq!(
+            kept(files, &policy(ReleasePreference::Volumes, "")),
+            ["en c005", "raw v01"]
+        );
+    }
+}
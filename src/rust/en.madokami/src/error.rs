@@ -0,0 +1,54 @@
+use aidoku::error::{AidokuError, AidokuErrorKind, NodeError};
+
+/// Why a request to Madokami failed, as distinct from a page that loaded
+/// fine but had nothing on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MadokamiError {
+    /// The path doesn't exist (404), e.g. a series that was moved or removed.
+    NotFound,
+    /// Credentials are missing or were rejected (401/403).
+    Unauthenticated,
+    /// Still rate limited (429) after backing off and retrying.
+    RateLimited,
+    /// The server still returned a 5xx status after retrying.
+    ServerError(i32),
+    /// The site answered with its maintenance page.
+    Maintenance,
+    /// Any other unexpected status.
+    UnexpectedStatus(i32),
+    /// None of the mirrors responded.
+    NoResponse,
+}
+
+impl MadokamiError {
+    /// Maps an HTTP status to an error, or `None` for a successful response.
+    pub fn from_status(status: i32) -> Option<Self> {
+        match status {
+            200..=299 => None,
+            401 | 403 => Some(Self::Unauthenticated),
+            404 | 410 => Some(Self::NotFound),
+            429 => Some(Self::RateLimited),
+            500..=599 => Some(Self::ServerError(status)),
+            _ => Some(Self::UnexpectedStatus(status)),
+        }
+    }
+}
+
+impl From<MadokamiError> for AidokuError {
+    /// `AidokuError` can't carry a message, so errors map to the closest
+    /// kind: a missing page to `DefaultNotFound`, the maintenance page to a
+    /// parse error, since it isn't the page that was asked for, and failed
+    /// requests to `Unimplemented`.
+    fn from(error: MadokamiError) -> Self {
+        let reason = match error {
+            MadokamiError::NotFound => AidokuErrorKind::DefaultNotFound,
+            MadokamiError::Maintenance => AidokuErrorKind::NodeError(NodeError::ParseError),
+            MadokamiError::Unauthenticated
+            | MadokamiError::RateLimited
+            | MadokamiError::ServerError(_)
+            | MadokamiError::UnexpectedStatus(_)
+            | MadokamiError::NoResponse => AidokuErrorKind::Unimplemented,
+        };
+        AidokuError { reason }
+    }
+}
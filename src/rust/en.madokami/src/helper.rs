@@ -18,17 +18,83 @@ pub fn decode_html_entities(input: &str) -> String {
         .replace("&amp;", "&")
 }
 
-pub fn clean_description(input: &str) -> String {
-    let decoded = decode_html_entities(input);
-    if let Some(end_idx) = decoded.find("//-->") {
-        if let Some(_start_idx) = decoded[..end_idx].rfind("<!--") {
-            let after_script = decoded[end_idx + 5..].trim();
-            if !after_script.is_empty() {
-                return after_script.to_string();
+/// Removes every `open ... close` block, matching case-insensitively. An
+/// unterminated block runs to the end of the input.
+fn remove_blocks(input: &str, open: &str, close: &str) -> String {
+    let lower = input.to_ascii_lowercase();
+    let mut result = String::with_capacity(input.len());
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find(open).map(|i| pos + i) {
+        result.push_str(&input[pos..start]);
+        pos = match lower[start + open.len()..].find(close) {
+            Some(end) => start + open.len() + end + close.len(),
+            None => input.len(),
+        };
+    }
+    result.push_str(&input[pos..]);
+    result
+}
+
+/// Replaces HTML tags with nothing, or a line break for tags that start a new
+/// line. A `<` that doesn't open a tag, like in `<3`, is kept.
+fn strip_tags(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('<') {
+        result.push_str(&rest[..start]);
+        let tag = &rest[start + 1..];
+        let opens_tag = tag
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        match tag.find('>') {
+            Some(end) if opens_tag => {
+                let name: String = tag[..end]
+                    .trim_start_matches('/')
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .collect::<String>()
+                    .to_ascii_lowercase();
+                if matches!(name.as_str(), "br" | "p" | "div" | "li") {
+                    result.push('\n');
+                }
+                rest = &tag[end + 1..];
             }
+            _ => {
+                result.push('<');
+                rest = tag;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Turns a series description from the site's meta tags into plain text.
+///
+/// Scanlator notes often carry inline `<!-- ... //-->` scripts and markup, so
+/// all scripts, comments and tags are removed, runs of whitespace collapsed and
+/// at most one blank line kept between paragraphs.
+pub fn clean_description(input: &str) -> String {
+    let decoded = decode_html_entities(input).replace("\r\n", "\n");
+    let text = remove_blocks(&decoded, "<script", "</script>");
+    let text = remove_blocks(&text, "<style", "</style>");
+    let text = remove_blocks(&text, "<!--", "-->");
+    let text = strip_tags(&text);
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        let after_blank = lines.last().map(|l| l.is_empty()).unwrap_or(true);
+        if line.is_empty() && after_blank {
+            continue;
         }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
     }
-    decoded
+    lines.join("\n")
 }
 
 pub fn url_decode(input: &str) -> String {
@@ -59,8 +125,12 @@ fn hex_val(b: u8) -> Option<u8> {
 }
 
 pub fn url_encode(input: &str) -> String {
+    encode_bytes(input.bytes())
+}
+
+fn encode_bytes(bytes: impl Iterator<Item = u8>) -> String {
     let mut encoded = String::new();
-    for byte in input.bytes() {
+    for byte in bytes {
         match byte {
             b'a'..=b'z'
             | b'A'..=b'Z'
@@ -78,14 +148,43 @@ pub fn url_encode(input: &str) -> String {
     encoded
 }
 
+/// Re-encodes each segment of a path the way the listings link to series, and
+/// drops the trailing slash, so a path copied from a browser gets the same id
+/// as the series it points to. Escapes are decoded to bytes first, so
+/// multi-byte characters survive.
+pub fn normalize_path(path: &str) -> String {
+    path.trim_end_matches('/')
+        .split('/')
+        .map(|segment| {
+            let bytes = segment.as_bytes();
+            let mut decoded = Vec::with_capacity(bytes.len());
+            let mut i = 0;
+            while i < bytes.len() {
+                if bytes[i] == b'%' && i + 2 < bytes.len() {
+                    if let (Some(h1), Some(h2)) = (hex_val(bytes[i + 1]), hex_val(bytes[i + 2])) {
+                        decoded.push(h1 << 4 | h2);
+                        i += 3;
+                        continue;
+                    }
+                }
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+            encode_bytes(decoded.into_iter())
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+const FILE_EXTENSIONS: &[&str] = &[
+    ".cbz", ".zip", ".cbr", ".rar", ".7z", ".pdf", ".epub",
+    ".png", ".jpg", ".jpeg", ".gif", ".xml", ".txt",
+];
+
 pub fn clean_filename(filename: &str) -> String {
-    const EXTENSIONS: &[&str] = &[
-        ".cbz", ".zip", ".cbr", ".rar", ".7z", ".pdf", ".epub",
-        ".png", ".jpg", ".jpeg", ".gif", ".xml", ".txt",
-    ];
     let mut cleaned = filename.to_string();
     let cleaned_lower = cleaned.to_lowercase();
-    for ext in EXTENSIONS {
+    for ext in FILE_EXTENSIONS {
         if cleaned_lower.ends_with(ext) {
             cleaned.truncate(cleaned.len() - ext.len());
             break;
@@ -286,3 +385,397 @@ pub fn parse_chapter_info(filename: &str, manga_title: &str) -> ChapterInfo {
 
     info
 }
+
+/// Collects the contents of the `(...)` and `[...]` tags in a filename.
+fn bracket_tags(filename: &str) -> Vec<String> {
+    let name = clean_filename(&url_decode(filename));
+    let mut tags = Vec::new();
+    let mut rest = name.as_str();
+    while let Some(start) = rest.find(['(', '[']) {
+        let close = if rest[start..].starts_with('(') { ')' } else { ']' };
+        let Some(len) = rest[start + 1..].find(close) else {
+            break;
+        };
+        let tag = rest[start + 1..start + 1 + len].trim();
+        if !tag.is_empty() {
+            tags.push(tag.to_string());
+        }
+        rest = &rest[start + len + 2..];
+    }
+    tags
+}
+
+/// Filename tags that mark a language, and the language code they map to.
+const LANGUAGE_TAGS: &[(&str, &str)] = &[
+    ("en", "en"),
+    ("eng", "en"),
+    ("english", "en"),
+    ("jp", "ja"),
+    ("jpn", "ja"),
+    ("ja", "ja"),
+    ("japanese", "ja"),
+    ("kr", "ko"),
+    ("kor", "ko"),
+    ("ko", "ko"),
+    ("korean", "ko"),
+    ("cn", "zh"),
+    ("chi", "zh"),
+    ("zh", "zh"),
+    ("chinese", "zh"),
+];
+
+fn language_for_tag(tag: &str) -> Option<&'static str> {
+    let tag = tag.to_lowercase();
+    LANGUAGE_TAGS
+        .iter()
+        .find(|(name, _)| *name == tag)
+        .map(|(_, code)| *code)
+}
+
+/// Extracts the release group from a filename, e.g. `danke-Empire` from
+/// `Berserk v01 (2003) (Digital) (danke-Empire).cbz`.
+///
+/// The last bracketed tag that isn't a year, a language or a known edition
+/// marker wins.
+pub fn parse_group(filename: &str) -> String {
+    const IGNORED: &[&str] = &["digital", "complete", "color", "colored", "f", "hq", "raw"];
+    bracket_tags(filename)
+        .into_iter()
+        .rev()
+        .find(|tag| {
+            !tag.chars().all(|c| c.is_ascii_digit() || c == '-')
+                && !IGNORED.contains(&tag.to_lowercase().as_str())
+                && language_for_tag(tag).is_none()
+        })
+        .unwrap_or_default()
+}
+
+/// Infers the language of a file from its path, filename and the genres of
+/// its series.
+///
+/// An explicit language tag in the filename (`[JP]`, `(Korean)`) wins. Raws,
+/// whether under `/Raws` or tagged `[Raw]`, are assumed to be Japanese unless
+/// the series is tagged as manhwa or manhua. Everything else is English.
+pub fn detect_language(path: &str, filename: &str, genres: &[String]) -> &'static str {
+    let tags = bracket_tags(filename);
+    if let Some(code) = tags.iter().find_map(|tag| language_for_tag(tag)) {
+        return code;
+    }
+    let is_raw = path.starts_with("/Raws/")
+        || path.starts_with("/reader/Raws/")
+        || tags.iter().any(|tag| tag.eq_ignore_ascii_case("raw"));
+    if !is_raw {
+        return "en";
+    }
+    if genres.iter().any(|g| g.eq_ignore_ascii_case("manhwa")) {
+        "ko"
+    } else if genres.iter().any(|g| g.eq_ignore_ascii_case("manhua")) {
+        "zh"
+    } else {
+        "ja"
+    }
+}
+
+/// Parses a human readable file size such as `45.2 MB` into bytes.
+/// Returns 0 if the size can't be parsed.
+pub fn parse_size(input: &str) -> u64 {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let value = input[..split].parse::<f64>().unwrap_or(0.0);
+    let multiplier = match input[split..].trim().to_lowercase().as_str() {
+        "kb" | "kib" | "k" => 1024.0,
+        "mb" | "mib" | "m" => 1024.0 * 1024.0,
+        "gb" | "gib" | "g" => 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+    (value * multiplier) as u64
+}
+
+/// Normalises a path for comparison by dropping the query string, fragment
+/// and trailing slash, and percent-decoding what's left. Links to the same
+/// file that were encoded differently produce the same key.
+pub fn path_key(path: &str) -> String {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    url_decode(path.trim_end_matches('/'))
+}
+
+/// Whether the last segment of a path names a downloadable file rather than a
+/// directory.
+pub fn is_file_path(path: &str) -> bool {
+    let name = path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    FILE_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+/// The hosts Madokami is served from, as listed in `source.json`.
+const KNOWN_HOSTS: [&str; 4] = [
+    "https://manga.madokami.al",
+    "https://madokami.al",
+    "https://manga.madokami.com",
+    "https://madokami.com",
+];
+
+/// Removes the scheme from a URL, returning `None` if it isn't `http(s)`.
+fn strip_scheme(url: &str) -> Option<&str> {
+    url.strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+}
+
+/// Removes the scheme and host from a Madokami URL, leaving only the path.
+/// The host has to be one of the configured `hosts` or one Madokami is known
+/// to be served from, and is matched in full, so `https://madokami.al.example`
+/// isn't mistaken for `https://madokami.al`. Either scheme is accepted. Input
+/// that isn't a Madokami URL is returned unchanged.
+pub fn strip_host(url: &str, hosts: &[String]) -> String {
+    let Some(rest) = strip_scheme(url) else {
+        return url.to_string();
+    };
+    hosts
+        .iter()
+        .map(String::as_str)
+        .chain(KNOWN_HOSTS)
+        .filter_map(strip_scheme)
+        .find_map(|host| {
+            let path = rest
+                .get(..host.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(host))
+                .map(|_| &rest[host.len()..])?;
+            (path.is_empty() || path.starts_with('/')).then_some(path)
+        })
+        .unwrap_or(url)
+        .to_string()
+}
+
+/// Validates a user supplied base URL, returning it without a trailing slash.
+/// Only `http` and `https` URLs with a host and no query string are accepted.
+pub fn normalize_base_url(input: &str) -> Option<String> {
+    let url = input.trim().trim_end_matches('/');
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let host = rest.split('/').next().unwrap_or_default();
+    if host.is_empty()
+        || !host.contains('.')
+        || rest.contains(|c: char| c.is_whitespace() || c == '?' || c == '#')
+    {
+        return None;
+    }
+    Some(url.to_string())
+}
+
+/// Returns the given 1-based page of `items`, and whether there are more pages
+/// after it.
+pub fn paginate<T>(items: Vec<T>, page: i32, page_size: usize) -> (Vec<T>, bool) {
+    let start = (page.max(1) as usize - 1) * page_size;
+    let has_more = items.len() > start + page_size;
+    let page = items.into_iter().skip(start).take(page_size).collect();
+    (page, has_more)
+}
+
+/// A link from a series page to its entry on a tracking site.
+#[derive(Clone)]
+pub struct TrackerLink {
+    /// Display name of the tracker, e.g. `MangaUpdates`.
+    pub tracker: &'static str,
+    /// The series' ID on the tracker.
+    pub id: String,
+}
+
+/// The value of `key` in the query string of `url`, if it's there.
+fn query_param<'a>(url: &'a str, key: &str) -> Option<&'a str> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('#')
+        .next()?
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value)
+}
+
+/// Recognises a MangaUpdates, MyAnimeList or AniList series URL and extracts
+/// the series ID from it.
+pub fn parse_tracker_link(url: &str) -> Option<TrackerLink> {
+    let lower = url.to_ascii_lowercase();
+    let (tracker, id) = if lower.contains("mangaupdates.com") {
+        // Old style `series.html?id=1234`, or new style `/series/{id}/{slug}`.
+        let id = match query_param(url, "id") {
+            Some(id) => id,
+            None => url.split("/series/").nth(1)?.split(['/', '?', '#']).next().unwrap_or_default(),
+        };
+        ("MangaUpdates", id)
+    } else if lower.contains("myanimelist.net/manga/") {
+        let id = url.split("/manga/").nth(1)?.split(['/', '?', '#']).next().unwrap_or_default();
+        ("MyAnimeList", id)
+    } else if lower.contains("anilist.co/manga/") {
+        let id = url.split("/manga/").nth(1)?.split(['/', '?', '#']).next().unwrap_or_default();
+        ("AniList", id)
+    } else {
+        return None;
+    };
+    if id.is_empty() {
+        return None;
+    }
+    Some(TrackerLink {
+        tracker,
+        id: id.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_description_removes_scripts() {
+        let input = "<!-- var a = 1; //-->Story &amp; art.<!-- b(); //--> More.";
+        assert_eq!(clean_description(input), "Story & art. More.");
+    }
+
+    #[test]
+    fn test_clean_description_line_breaks() {
+        let input = "  First   line<br/>Second\tline<br><br><br>Third  <b>bold</b>\n\n";
+        assert_eq!(clean_description(input), "First line\nSecond line\n\nThird bold");
+    }
+
+    #[test]
+    fn test_clean_description_keeps_stray_angle_brackets() {
+        assert_eq!(clean_description("We <3 this & 1 < 2"), "We <3 this & 1 < 2");
+    }
+
+    fn hosts() -> Vec<String> {
+        Vec::from([String::from("https://mirror.example/madokami")])
+    }
+
+    #[test]
+    fn test_strip_host_known_hosts() {
+        for url in [
+            "https://manga.madokami.al/Manga/A/AB/ABC",
+            "http://madokami.com/Manga/A/AB/ABC",
+            "https://Madokami.AL/Manga/A/AB/ABC",
+            "https://mirror.example/madokami/Manga/A/AB/ABC",
+        ] {
+            assert_eq!(strip_host(url, &hosts()), "/Manga/A/AB/ABC");
+        }
+        assert_eq!(strip_host("https://madokami.al", &hosts()), "");
+    }
+
+    #[test]
+    fn test_strip_host_requires_exact_host() {
+        for url in [
+            "https://madokami.al.evil.example/Manga/A",
+            "https://notmadokami.al/Manga/A",
+            "https://madokami.example/Manga/A",
+            "https://mirror.example/madokami2/Manga/A",
+            "/Manga/A/AB/ABC",
+        ] {
+            assert_eq!(strip_host(url, &hosts()), url);
+        }
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path("/Manga/A/AB/ABC/Abc Def (Ghi)/"),
+            "/Manga/A/AB/ABC/Abc%20Def%20%28Ghi%29"
+        );
+        assert_eq!(
+            normalize_path("/Manga/A/AB/ABC/Abc%20Def%20(Ghi)"),
+            "/Manga/A/AB/ABC/Abc%20Def%20%28Ghi%29"
+        );
+        assert_eq!(normalize_path("/Manga/%e3%81%82/%E3%81%82"), "/Manga/%E3%81%82/%E3%81%82");
+        assert_eq!(normalize_path("/Manga/100%"), "/Manga/100%25");
+    }
+
+    fn genres(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| String::from(*name)).collect()
+    }
+
+    #[test]
+    fn test_detect_language_raws() {
+        let path = "/Raws/S/SO/Solo Leveling";
+        let file = "Solo Leveling v01.zip";
+        assert_eq!(detect_language(path, file, &genres(&["Manhwa", "Action"])), "ko");
+        assert_eq!(detect_language(path, file, &genres(&["manhua"])), "zh");
+        assert_eq!(detect_language(path, file, &[]), "ja");
+        assert_eq!(detect_language("/Manga/S/SO/Solo Leveling", file, &[]), "en");
+    }
+
+    #[test]
+    fn test_detect_language_tag_overrides_path() {
+        assert_eq!(
+            detect_language("/Raws/T/TO/Tower", "Tower c001 [KR].zip", &genres(&["Manhua"])),
+            "ko"
+        );
+        assert_eq!(detect_language("/Manga/B/BE/Berserk", "Berserk c001 (Raw).zip", &[]), "ja");
+        assert_eq!(detect_language("/Manga/B/BE/Berserk", "Berserk c001 [JP].zip", &[]), "ja");
+    }
+
+    #[test]
+    fn test_bracket_tags() {
+        assert_eq!(
+            bracket_tags("Berserk%20v01%20(2003)%20[Group].cbz"),
+            ["2003", "Group"]
+        );
+        assert!(bracket_tags("Berserk v01.cbz").is_empty());
+    }
+
+    #[test]
+    fn test_parse_group_skips_metadata() {
+        assert_eq!(
+            parse_group("Berserk v01 (2003) (Digital) (danke-Empire).cbz"),
+            "danke-Empire"
+        );
+        assert_eq!(parse_group("Berserk v01 (Group) (Digital) (2003) [EN].cbz"), "Group");
+        assert_eq!(parse_group("Berserk v01 (2003) (Digital) [JP].cbz"), "");
+        assert_eq!(parse_group("Berserk v01.cbz"), "");
+    }
+
+    fn tracker(url: &str) -> Option<(&'static str, String)> {
+        parse_tracker_link(url).map(|link| (link.tracker, link.id))
+    }
+
+    #[test]
+    fn test_parse_tracker_link() {
+        assert_eq!(
+            tracker("https://www.mangaupdates.com/series.html?id=1234"),
+            Some(("MangaUpdates", String::from("1234")))
+        );
+        assert_eq!(
+            tracker("https://www.mangaupdates.com/series/abc123/berserk?utm_id=9"),
+            Some(("MangaUpdates", String::from("abc123")))
+        );
+        assert_eq!(
+            tracker("https://myanimelist.net/manga/2/Berserk"),
+            Some(("MyAnimeList", String::from("2")))
+        );
+        assert_eq!(
+            tracker("https://anilist.co/manga/30002/?x=1"),
+            Some(("AniList", String::from("30002")))
+        );
+        assert_eq!(tracker("https://example.com/manga/2"), None);
+        assert_eq!(tracker("https://myanimelist.net/manga/"), None);
+    }
+
+    #[test]
+    fn test_parse_tracker_link_non_ascii() {
+        // `İ` grows when lowercased, which mustn't shift the offsets.
+        assert_eq!(
+            tracker("https://www.mangaupdates.com/series/xyz9/İİİİ"),
+            Some(("MangaUpdates", String::from("xyz9")))
+        );
+        assert_eq!(
+            tracker("https://www.mangaupdates.com/İİ/series.html?id=42"),
+            Some(("MangaUpdates", String::from("42")))
+        );
+    }
+}
@@ -1,67 +1,141 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 extern crate alloc;
 
 use aidoku::{
     error::Result,
     prelude::*,
-    std::{
-        defaults::defaults_get,
-        net::{HttpMethod, Request},
-        String, Vec,
-    },
-    Chapter, DeepLink, Filter, FilterType, Manga, MangaPageResult, MangaStatus, MangaViewer, Page,
+    std::{net::Request, String, Vec},
+    Chapter, DeepLink, Filter, Listing, Manga, MangaPageResult, MangaViewer, Page,
 };
-use base64::{engine::general_purpose, Engine};
 use alloc::format;
 
+mod cache;
+mod cell;
+mod chapters;
+mod error;
 mod helper;
+mod listing;
+mod net;
+mod recent;
+mod search;
+mod series;
+use chapters::*;
+use error::MadokamiError;
 use helper::*;
+use listing::*;
+use net::*;
+use search::*;
+use series::*;
 
-const BASE_URL: &str = "https://manga.madokami.al";
+#[initialize]
+fn initialize() {
+    configure_rate_limit();
+}
 
-/// Adds HTTP Basic authentication to the given request if credentials are provided.
-fn add_auth_to_request(mut request: Request) -> Request {
-    let username = defaults_get("username")
-        .and_then(|v| v.as_string())
-        .map(|s| s.read())
-        .unwrap_or_default();
-    let password = defaults_get("password")
-        .and_then(|v| v.as_string())
-        .map(|s| s.read())
-        .unwrap_or_default();
+/// Builds a chapter from a file in a series directory. `id` is the reader
+/// path used as the chapter ID, and `genres` are the series' tags if known.
+fn build_chapter(id: String, filename: &str, manga_title: &str, genres: &[String]) -> Chapter {
+    let info = parse_chapter_info(filename, manga_title);
+    Chapter {
+        url: format!("{}{}", base_url(), id),
+        lang: String::from(detect_language(&id, filename, genres)),
+        id,
+        title: clean_filename(&url_decode(filename)),
+        chapter: if info.chapter > 0.0 { info.chapter } else { -1.0 },
+        volume: if info.volume > 0.0 { info.volume } else { -1.0 },
+        scanlator: parse_group(filename),
+        ..Default::default()
+    }
+}
+
+/// Fetches every file in a series directory, before deduplication and sorting.
+fn fetch_chapter_files(id: &str) -> Result<Vec<ChapterFile>> {
+    let html = fetch_html(id)?;
+    remember_series_info(id, &html);
+    let manga_title = extract_manga_title(id);
+    // Only raws need the series' genres to tell their language apart, and
+    // the series page is usually cached by now.
+    let genres = if id.starts_with("/Raws/") {
+        fetch_series_info(id).map(|info| info.genres).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let mut files = Vec::new();
 
-    if !username.is_empty() && !password.is_empty() {
-        let auth = format!(
-            "Basic {}",
-            general_purpose::STANDARD.encode(format!("{}:{}", username, password))
-        );
-        request = request.header("Authorization", &auth);
+    for row in html.select("table#index-table > tbody > tr").array() {
+        if let Ok(node) = row.as_node() {
+            let title = node.select("td:nth-child(1) a").text().read();
+            if title.ends_with('/') || title.starts_with('!') {
+                continue;
+            }
+            let reader_href = node.select("td:nth-child(6) a").first().attr("href").read();
+            let url = match reader_href.rsplit("/reader").next() {
+                Some(reader_part) => format!("/reader{}", reader_part),
+                None => continue,
+            };
+            let mut chapter = build_chapter(url, &title, &manga_title, &genres);
+            chapter.date_updated = node
+                .select("td:nth-child(3)")
+                .text()
+                .as_date("yyyy-MM-dd HH:mm", None, None);
+            files.push(ChapterFile {
+                chapter,
+                size: parse_size(&node.select("td:nth-child(2)").text().read()),
+            });
+        }
     }
-    request
+    Ok(files)
 }
 
 #[handle_url]
 fn handle_url(url: String) -> Result<DeepLink> {
-    // Remove the base URL from the passed in URL so we work only with the path.
-    let url = url.replace(BASE_URL, "");
-    if url.starts_with("/reader") {
-        // If the URL starts with "/reader", we assume it points to a specific chapter.
-        // We take the part before "/reader" as the manga ID, and use the full URL as the chapter ID.
+    // Remove the host from the passed in URL so we work only with the path.
+    let url = strip_host(&url, &mirrors());
+    let path = url.strip_prefix("/reader").unwrap_or(&url);
+    // Encode the path the way the listings do, so the link opens the same
+    // series as the library entry.
+    let path = &normalize_path(path.split(['?', '#']).next().unwrap_or_default());
+
+    if is_file_path(path) {
+        // Reader and download URLs point to a file, whose directory is the
+        // manga.
+        let (manga_id, filename) = path.rsplit_once('/').unwrap_or_default();
+        let manga_id = String::from(manga_id);
+        let chapter_id = format!("/reader{}", path);
+
+        // Look the chapter up in the series so the link resolves to the same
+        // chapter object the library has, regardless of how the URL was
+        // encoded or which query string it carried.
+        let key = path_key(&chapter_id);
+        let chapter = fetch_chapter_files(&manga_id)
+            .ok()
+            .and_then(|files| {
+                files
+                    .into_iter()
+                    .map(|f| f.chapter)
+                    .find(|c| path_key(&c.id) == key)
+            })
+            .unwrap_or_else(|| {
+                build_chapter(chapter_id, filename, &extract_manga_title(&manga_id), &[])
+            });
+
         Ok(DeepLink {
             manga: Some(Manga {
-                id: String::from(url.split("/reader").next().unwrap_or_default()),
-                ..Default::default()
-            }),
-            chapter: Some(Chapter {
-                id: url,
+                title: extract_manga_title(&manga_id),
+                url: format!("{}{}", base_url(), manga_id),
+                id: manga_id,
                 ..Default::default()
             }),
+            chapter: Some(chapter),
         })
     } else {
         // Otherwise, we treat the URL as pointing only to a manga.
+        let id = String::from(path);
         Ok(DeepLink {
             manga: Some(Manga {
-                id: url,
+                title: extract_manga_title(&id),
+                url: format!("{}{}", base_url(), id),
+                id,
                 ..Default::default()
             }),
             ..Default::default()
@@ -70,154 +144,67 @@ fn handle_url(url: String) -> Result<DeepLink> {
 }
 
 #[get_manga_list]
-fn get_manga_list(filters: Vec<Filter>, _page: i32) -> Result<MangaPageResult> {
-    // Build URL based on whether a title filter is provided.
-    let url = if let Some(query) = filters
-        .into_iter()
-        .find(|f| matches!(f.kind, FilterType::Title))
-        .and_then(|f| f.value.as_string().ok())
-        .map(|s| url_encode(&s.read()))
-    {
-        format!("{}/search?q={}", BASE_URL, query)
-    } else {
-        format!("{}/recent", BASE_URL)
-    };
-
-    let request = Request::new(url.clone(), HttpMethod::Get)
-        .header(
-            "User-Agent",
-            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/115.0.0.0 Safari/537.36",
-        )
-        .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8");
-
-    let html = add_auth_to_request(request).html()?;
-
-    let selector = if url.ends_with("/recent") {
-        "table.mobile-files-table tbody tr td:nth-child(1) a:nth-child(1)"
-    } else {
-        "div.container table tbody tr td:nth-child(1) a:nth-child(1)"
-    };
-
-    let mut mangas = Vec::new();
-    for element in html.select(selector).array() {
-        if let Ok(node) = element.as_node() {
-            let path = node.attr("href").read();
-            if path.trim().is_empty() {
-                continue;
-            }
-            mangas.push(Manga {
-                id: path.clone(),
-                title: extract_manga_title(&path),
-                cover: String::new(),
-                url: format!("{}{}", BASE_URL, path),
-                status: MangaStatus::Unknown,
-                viewer: MangaViewer::Rtl,
-                ..Default::default()
-            });
-        }
+fn get_manga_list(filters: Vec<Filter>, page: i32) -> Result<MangaPageResult> {
+    match SearchOptions::from_filters(filters) {
+        Some(options) => Ok(MangaPageResult {
+            manga: search(&options)?,
+            has_more: false,
+        }),
+        None => latest_updates(page),
     }
+}
 
-    Ok(MangaPageResult {
-        manga: mangas,
-        has_more: false,
-    })
+#[get_manga_listing]
+fn get_manga_listing(listing: Listing, page: i32) -> Result<MangaPageResult> {
+    match listing.name.as_str() {
+        "Browse" => browse(page),
+        "Random" => random(page),
+        _ => latest_updates(page),
+    }
 }
 
 #[get_chapter_list]
 fn get_chapter_list(id: String) -> Result<Vec<Chapter>> {
-    let html = add_auth_to_request(
-        Request::new(format!("{}{}", BASE_URL, id), HttpMethod::Get)
-    )
-    .html()?;
-    let manga_title = extract_manga_title(&id);
-    let mut chapters = Vec::new();
-
-    for row in html.select("table#index-table > tbody > tr").array() {
-        if let Ok(node) = row.as_node() {
-            let title = node.select("td:nth-child(1) a").text().read();
-            if title.ends_with('/') || title.starts_with('!') {
-                continue;
-            }
-            let base_url = node.select("td:nth-child(6) a").first().attr("href").read();
-            let url = match base_url.split("/reader").last() {
-                Some(reader_part) => format!("/reader{}", reader_part),
-                None => continue,
-            };
-            let date_updated = node
-                .select("td:nth-child(3)")
-                .text()
-                .as_date("yyyy-MM-dd HH:mm", None, None);
-            let info = parse_chapter_info(&title, &manga_title);
-            let chapter_number = if info.chapter > 0.0 { info.chapter } else { -1.0 };
-
-            chapters.push(Chapter {
-                id: url.clone(),
-                title: clean_filename(&url_decode(&title)),
-                chapter: chapter_number,
-                volume: if info.volume > 0.0 { info.volume } else { -1.0 },
-                date_updated,
-                url: format!("{}{}", BASE_URL, url),
-                ..Default::default()
-            });
-        }
+    let mut files = fetch_chapter_files(&id)?;
+    if let Some(policy) = DedupePolicy::from_settings() {
+        files = dedupe(files, &policy);
     }
-    chapters.reverse();
-    Ok(chapters)
+    sort_by_number(&mut files, |f| {
+        (f.chapter.volume, f.chapter.chapter, f.chapter.title.as_str())
+    });
+    Ok(files.into_iter().map(|f| f.chapter).collect())
 }
 
 #[get_manga_details]
 fn get_manga_details(id: String) -> Result<Manga> {
-    let mut authors = Vec::new();
-    let mut genres = Vec::new();
-    let mut status = MangaStatus::Unknown;
-    let mut cover_url = String::new();
-    let mut parent_description = String::new();
-
-    let dir_name = id.trim_matches('/').rsplit('/').next().map(url_decode).unwrap_or_default();
-
-    if let Some(parent_path) = get_parent_path(&id) {
-        if let Ok(parent_html) = add_auth_to_request(
-            Request::new(format!("{}{}", BASE_URL, parent_path), HttpMethod::Get)
-        ).html() {
-            cover_url = parent_html
-                .select("div.manga-info img[itemprop=\"image\"]")
-                .attr("src")
-                .read();
-
-            authors = parent_html
-                .select("a[itemprop=\"author\"]")
-                .array()
-                .filter_map(|n| n.as_node().ok().map(|node| node.text().read()))
-                .collect();
-
-            genres = parent_html
-                .select("div.genres a.tag")
-                .array()
-                .filter_map(|n| n.as_node().ok().map(|node| node.text().read()))
-                .collect();
-
-            parent_description = {
-                let og_desc = parent_html
-                    .select("meta[property=\"og:description\"]")
-                    .attr("content")
-                    .read();
-                let desc = if !og_desc.is_empty() {
-                    og_desc
-                } else {
-                    parent_html
-                        .select("meta[name=\"description\"]")
-                        .attr("content")
-                        .read()
-                };
-                clean_description(&desc)
-            };
+    // Subdirectories like `!Extras` take their metadata from the series page
+    // above them, which may be missing without the subdirectory being gone.
+    let is_subdirectory = get_parent_path(&id).as_deref() != Some(id.trim_end_matches('/'));
+    let info = match fetch_series_info(&id) {
+        Ok(info) => Some(info),
+        Err(MadokamiError::NotFound) if is_subdirectory => None,
+        Err(error) => return Err(error.into()),
+    };
 
-            if parent_html.select("span.scanstatus").text().read() == "Yes" {
-                status = MangaStatus::Completed;
+    let title = extract_manga_title(&id);
+    // The directory name only tells something new if it isn't just the title,
+    // e.g. for `!Extras` or VIZBIG subdirectories.
+    let dir_name = id
+        .trim_matches('/')
+        .rsplit('/')
+        .next()
+        .map(url_decode)
+        .filter(|name| clean_filename(name).to_lowercase() != title.to_lowercase())
+        .unwrap_or_default();
+    let mut parent_description = info.as_ref().map(|i| i.description.clone()).unwrap_or_default();
+    if let Some(metadata) = info.as_ref().map(|i| i.matching_metadata()) {
+        if !metadata.is_empty() {
+            if !parent_description.is_empty() {
+                parent_description.push_str("\n\n");
             }
+            parent_description.push_str(&metadata);
         }
     }
-
     let description = if !dir_name.is_empty() && !parent_description.is_empty() {
         format!("{}\n\n{}", dir_name, parent_description)
     } else if !dir_name.is_empty() {
@@ -226,32 +213,33 @@ fn get_manga_details(id: String) -> Result<Manga> {
         parent_description
     };
 
-    Ok(Manga {
+    let mut manga = Manga {
         id: id.clone(),
-        title: extract_manga_title(&id),
-        author: authors.join(", "),
-        cover: cover_url,
-        categories: genres,
-        status,
+        title,
         description,
-        url: format!("{}{}", BASE_URL, id),
+        url: format!("{}{}", base_url(), id),
         viewer: MangaViewer::Rtl,
         ..Default::default()
-    })
+    };
+    if let Some(info) = info {
+        manga.author = info.authors.join(", ");
+        manga.cover = info.cover;
+        manga.categories = info.genres;
+        manga.status = info.status;
+    }
+    Ok(manga)
 }
 
 #[get_page_list]
 fn get_page_list(_manga_id: String, chapter_id: String) -> Result<Vec<Page>> {
     let chapter_id = chapter_id.split("?ch=").next().unwrap_or(&chapter_id);
-    let html = add_auth_to_request(
-        Request::new(format!("{}{}", BASE_URL, chapter_id), HttpMethod::Get)
-    )
-    .html()?;
+    let html = fetch_html(chapter_id)?;
 
     let reader = html.select("div#reader");
     let path = reader.attr("data-path").read();
     let files = reader.attr("data-files").read();
 
+    let host = base_url();
     let mut pages = Vec::new();
     if let Ok(file_list) = aidoku::std::json::parse(files.as_bytes()) {
         if let Ok(array) = file_list.as_array() {
@@ -261,7 +249,7 @@ fn get_page_list(_manga_id: String, chapter_id: String) -> Result<Vec<Page>> {
                         index: index as i32,
                         url: format!(
                             "{}/reader/image?path={}&file={}",
-                            BASE_URL,
+                            host,
                             url_encode(&path),
                             url_encode(&filename.read())
                         ),
@@ -276,7 +264,7 @@ fn get_page_list(_manga_id: String, chapter_id: String) -> Result<Vec<Page>> {
 
 #[modify_image_request]
 fn modify_image_request(request: Request) -> Request {
-    add_auth_to_request(request)
-        .header("Referer", BASE_URL)
+    with_headers(request)
+        .header("Referer", &base_url())
         .header("Accept", "image/*")
 }
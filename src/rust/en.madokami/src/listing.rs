@@ -0,0 +1,189 @@
+use aidoku::{
+    error::Result,
+    prelude::format,
+    std::{current_date, String, Vec},
+    Manga, MangaPageResult, MangaStatus, MangaViewer,
+};
+
+use crate::{
+    cell::SessionCell,
+    error::MadokamiError,
+    helper::{extract_manga_title, paginate},
+    net::{base_url, fetch_html},
+    recent::parse_recent,
+};
+
+/// Series shown per page of a listing.
+pub const PAGE_SIZE: usize = 20;
+
+/// Directory browsed by the Browse and Random listings.
+const BROWSE_ROOT: &str = "/Manga";
+
+/// Series directories sit below three levels of prefix directories, e.g.
+/// `/Manga/B/BE/BERS/Berserk`.
+const SERIES_DEPTH: usize = 5;
+
+/// How many prefix directories the Random listing samples per page.
+const RANDOM_WALKS: usize = 3;
+
+/// The Random listing is endless in principle, but stop after a few pages.
+const RANDOM_PAGES: i32 = 5;
+
+/// Directories still to be visited by the Browse listing, used as a stack so
+/// each page continues where the previous one stopped.
+static BROWSE_QUEUE: SessionCell<Vec<String>> = SessionCell::new(Vec::new());
+
+/// Builds a list entry for a series directory.
+pub fn series_manga(path: String, base_url: &str) -> Manga {
+    Manga {
+        title: extract_manga_title(&path),
+        url: format!("{}{}", base_url, path),
+        id: path,
+        status: MangaStatus::Unknown,
+        viewer: MangaViewer::Rtl,
+        ..Default::default()
+    }
+}
+
+fn depth(path: &str) -> usize {
+    path.split('/').filter(|s| !s.is_empty()).count()
+}
+
+/// Lists the subdirectories of a directory, in the order the site shows them.
+fn fetch_subdirectories(path: &str) -> core::result::Result<Vec<String>, MadokamiError> {
+    let html = fetch_html(path)?;
+    let mut dirs = Vec::new();
+    for row in html.select("table#index-table > tbody > tr").array() {
+        let Ok(node) = row.as_node() else {
+            continue;
+        };
+        let link = node.select("td:nth-child(1) a");
+        let name = link.text().read();
+        if !name.ends_with('/') || name.starts_with('!') {
+            continue;
+        }
+        let href = link.attr("href").read();
+        if !href.is_empty() {
+            dirs.push(String::from(href.trim_end_matches('/')));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Recent uploads grouped by series, newest first. The site shows the whole
+/// feed on one page, so it's paginated here.
+pub fn latest_updates(page: i32) -> Result<MangaPageResult> {
+    let html = fetch_html("/recent")?;
+    let (series, has_more) = paginate(parse_recent(&html), page, PAGE_SIZE);
+    let base_url = base_url();
+    Ok(MangaPageResult {
+        manga: series
+            .into_iter()
+            .map(|s| s.into_manga(&base_url))
+            .collect(),
+        has_more,
+    })
+}
+
+/// Every series under [`BROWSE_ROOT`] in directory order, walking down the
+/// prefix directories a page at a time.
+pub fn browse(page: i32) -> Result<MangaPageResult> {
+    if page <= 1 {
+        BROWSE_QUEUE.with(|queue| {
+            queue.clear();
+            queue.push(String::from(BROWSE_ROOT));
+        });
+    }
+
+    let mut series = Vec::new();
+    while series.len() < PAGE_SIZE {
+        let Some(dir) = BROWSE_QUEUE.with(|queue| queue.pop()) else {
+            break;
+        };
+        if depth(&dir) >= SERIES_DEPTH {
+            series.push(dir);
+            continue;
+        }
+        match fetch_subdirectories(&dir) {
+            Ok(mut children) => {
+                // Push in reverse so children are popped in directory order.
+                children.reverse();
+                BROWSE_QUEUE.with(|queue| queue.extend(children));
+            }
+            Err(error) => {
+                // Keep the directory so the page can be retried.
+                BROWSE_QUEUE.with(|queue| queue.push(dir));
+                return Err(error.into());
+            }
+        }
+    }
+
+    let base_url = base_url();
+    Ok(MangaPageResult {
+        manga: series
+            .into_iter()
+            .map(|path| series_manga(path, &base_url))
+            .collect(),
+        has_more: BROWSE_QUEUE.with(|queue| !queue.is_empty()),
+    })
+}
+
+/// A xorshift generator seeded from the clock, which is plenty for picking
+/// directories.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/// Series from a few randomly chosen prefix directories.
+pub fn random(page: i32) -> Result<MangaPageResult> {
+    let mut rng = Rng::new(current_date().to_bits() ^ page as u64);
+    let mut series: Vec<String> = Vec::new();
+
+    for _ in 0..RANDOM_WALKS {
+        let mut dir = String::from(BROWSE_ROOT);
+        while depth(&dir) < SERIES_DEPTH - 1 {
+            let children = fetch_subdirectories(&dir)?;
+            if children.is_empty() {
+                break;
+            }
+            dir = children[rng.below(children.len())].clone();
+        }
+        if depth(&dir) != SERIES_DEPTH - 1 {
+            continue;
+        }
+        for path in fetch_subdirectories(&dir)? {
+            if !series.contains(&path) {
+                series.push(path);
+            }
+        }
+        if series.len() >= PAGE_SIZE {
+            break;
+        }
+    }
+
+    for i in (1..series.len()).rev() {
+        let j = rng.below(i + 1);
+        series.swap(i, j);
+    }
+    series.truncate(PAGE_SIZE);
+
+    let base_url = base_url();
+    Ok(MangaPageResult {
+        manga: series
+            .into_iter()
+            .map(|path| series_manga(path, &base_url))
+            .collect(),
+        has_more: page < RANDOM_PAGES,
+    })
+}
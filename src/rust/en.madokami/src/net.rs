@@ -0,0 +1,178 @@
+use aidoku::{
+    std::{
+        defaults::defaults_get,
+        html::Node,
+        net::{set_rate_limit, set_rate_limit_period, HttpMethod, Request},
+        String, Vec,
+    },
+};
+use alloc::format;
+use base64::{engine::general_purpose, Engine};
+
+use crate::{error::MadokamiError, helper::normalize_base_url};
+
+const DEFAULT_BASE_URL: &str = "https://manga.madokami.al";
+
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/115.0.0.0 Safari/537.36";
+
+/// How many times a request is retried on a 429 or 5xx response before moving
+/// on to the next mirror.
+const MAX_RETRIES: u32 = 2;
+
+/// Upper bound in seconds for a single backoff, including any `Retry-After`
+/// the server asks for.
+const MAX_BACKOFF: i32 = 8;
+
+fn setting_string(key: &str) -> String {
+    defaults_get(key)
+        .and_then(|v| v.as_string())
+        .map(|s| s.read())
+        .unwrap_or_default()
+}
+
+/// The configured base URL, falling back to the default if the setting is
+/// empty or not a valid URL.
+pub fn base_url() -> String {
+    normalize_base_url(&setting_string("baseUrl"))
+        .unwrap_or_else(|| String::from(DEFAULT_BASE_URL))
+}
+
+/// The base URL followed by any valid mirrors from settings, in the order
+/// they should be tried.
+pub fn mirrors() -> Vec<String> {
+    let mut hosts = Vec::from([base_url()]);
+    for mirror in setting_string("mirrors")
+        .split([',', '\n', ' '])
+        .filter_map(normalize_base_url)
+    {
+        if !hosts.contains(&mirror) {
+            hosts.push(mirror);
+        }
+    }
+    hosts
+}
+
+/// Applies the per-session rate limit from settings. Changes take effect the
+/// next time the source is loaded. Also undoes a [`back_off`].
+pub fn configure_rate_limit() {
+    let limit = defaults_get("rateLimit")
+        .and_then(|v| v.as_int())
+        .unwrap_or(30);
+    if limit > 0 {
+        set_rate_limit(limit as i32);
+    } else {
+        // Disabled, but a backoff may have lowered the limit earlier.
+        set_rate_limit(i32::MAX);
+    }
+    set_rate_limit_period(60);
+}
+
+/// Holds the next request back for `seconds` by allowing a single request
+/// per period. The host rate limiter does the waiting, so the source doesn't
+/// have to.
+fn back_off(seconds: i32) {
+    set_rate_limit(1);
+    set_rate_limit_period(seconds);
+}
+
+/// Adds HTTP Basic authentication to the given request if credentials are provided.
+fn add_auth_to_request(mut request: Request) -> Request {
+    let username = setting_string("username");
+    let password = setting_string("password");
+
+    if !username.is_empty() && !password.is_empty() {
+        let auth = format!(
+            "Basic {}",
+            general_purpose::STANDARD.encode(format!("{}:{}", username, password))
+        );
+        request = request.header("Authorization", &auth);
+    }
+    request
+}
+
+/// Adds the configured user agent and credentials to a request. Every request
+/// the source makes, including image requests, goes through here.
+pub fn with_headers(request: Request) -> Request {
+    let user_agent = setting_string("userAgent");
+    let user_agent = if user_agent.trim().is_empty() {
+        DEFAULT_USER_AGENT
+    } else {
+        user_agent.trim()
+    };
+    add_auth_to_request(request.header("User-Agent", user_agent))
+}
+
+fn new_request(url: &str) -> Request {
+    with_headers(Request::new(url, HttpMethod::Get)).header(
+        "Accept",
+        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+    )
+}
+
+fn should_retry(status: i32) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// Sends a GET request for `path`, trying each mirror in order.
+///
+/// Rate limited and server error responses are retried on the same host with
+/// exponential backoff, honouring `Retry-After` when present, before falling
+/// back to the next mirror. The last response received is returned even if
+/// it was an error, so callers can inspect its status; an error is only
+/// returned if no mirror responded at all.
+pub fn get(path: &str) -> Result<Request, MadokamiError> {
+    let mut last = None;
+    let mut backed_off = false;
+    'hosts: for host in mirrors() {
+        let url = format!("{}{}", host, path);
+        for attempt in 0..=MAX_RETRIES {
+            let request = new_request(&url);
+            request.send();
+            let status = request.status_code();
+            if status <= 0 {
+                // No response from this host, try the next one.
+                break;
+            }
+            if !should_retry(status) {
+                last = Some(request);
+                break 'hosts;
+            }
+            if attempt < MAX_RETRIES {
+                let seconds = request
+                    .get_header("Retry-After")
+                    .and_then(|v| v.read().trim().parse::<i32>().ok())
+                    .unwrap_or(1 << attempt);
+                back_off(seconds.clamp(1, MAX_BACKOFF));
+                backed_off = true;
+            }
+            last = Some(request);
+        }
+    }
+    if backed_off {
+        configure_rate_limit();
+    }
+    last.ok_or(MadokamiError::NoResponse)
+}
+
+/// Fetches a page by path and parses it as HTML.
+///
+/// Error statuses are turned into a [`MadokamiError`] rather than parsed, as
+/// are pages that loaded but aren't what was asked for (the maintenance page,
+/// or a login form when the credentials were rejected).
+pub fn fetch_html(path: &str) -> Result<Node, MadokamiError> {
+    let request = get(path)?;
+    if let Some(error) = MadokamiError::from_status(request.status_code()) {
+        return Err(error);
+    }
+    let html = request
+        .html()
+        .map_err(|_| MadokamiError::UnexpectedStatus(200))?;
+    let title = html.select("head > title").text().read().to_lowercase();
+    if title.contains("maintenance") {
+        return Err(MadokamiError::Maintenance);
+    }
+    if !html.select("form input[type=password]").array().is_empty() {
+        return Err(MadokamiError::Unauthenticated);
+    }
+    Ok(html)
+}
@@ -0,0 +1,84 @@
+use aidoku::{
+    prelude::format,
+    std::{html::Node, String, Vec},
+    Manga,
+};
+
+use crate::{
+    helper::{clean_filename, url_decode},
+    listing::series_manga,
+};
+
+/// A series with uploads in the recent feed, grouped from one or more rows.
+pub struct RecentSeries {
+    pub path: String,
+    /// File name of the newest upload.
+    pub newest_file: String,
+    /// Upload time of the newest file, as shown on the site.
+    pub newest_time: String,
+    pub newest_date: f64,
+    /// How many of the recent uploads belong to this series.
+    pub uploads: usize,
+}
+
+impl RecentSeries {
+    pub fn into_manga(self, base_url: &str) -> Manga {
+        let mut description = format!(
+            "Latest upload: {}",
+            clean_filename(&url_decode(&self.newest_file))
+        );
+        if !self.newest_time.is_empty() {
+            description.push_str(&format!("\nUploaded {}", self.newest_time));
+        }
+        if self.uploads > 1 {
+            description.push_str(&format!("\n{} new files", self.uploads));
+        }
+        Manga {
+            description,
+            ..series_manga(self.path, base_url)
+        }
+    }
+}
+
+/// Parses the `/recent` page, grouping uploads by the series they belong to.
+///
+/// Each row links the series directory first and the uploaded file last, with
+/// the upload time in the third column. Series keep the order in which they
+/// first appear, so the most recently updated one comes first.
+pub fn parse_recent(html: &Node) -> Vec<RecentSeries> {
+    let mut series: Vec<RecentSeries> = Vec::new();
+    for row in html.select("table.mobile-files-table tbody tr").array() {
+        let Ok(row) = row.as_node() else {
+            continue;
+        };
+        let path = row.select("td:nth-child(1) a:nth-child(1)").attr("href").read();
+        if path.trim().is_empty() {
+            continue;
+        }
+        let file = row.select("td:nth-child(1) a").last().text().read();
+        let time = String::from(row.select("td:nth-child(3)").text().read().trim());
+        let date = row
+            .select("td:nth-child(3)")
+            .text()
+            .as_date("yyyy-MM-dd HH:mm", None, None);
+
+        match series.iter_mut().find(|s| s.path == path) {
+            Some(existing) => {
+                existing.uploads += 1;
+                if date > existing.newest_date {
+                    existing.newest_file = file;
+                    existing.newest_time = time;
+                    existing.newest_date = date;
+                }
+            }
+            None => series.push(RecentSeries {
+                path,
+                newest_file: file,
+                newest_time: time,
+                newest_date: date,
+                uploads: 1,
+            }),
+        }
+    }
+    series
+}
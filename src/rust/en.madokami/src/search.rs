@@ -0,0 +1,286 @@
+use aidoku::{
+    error::Result,
+    prelude::format,
+    std::{defaults::defaults_get, html::Node, String, Vec},
+    Filter, FilterType, Manga, MangaStatus,
+};
+
+use crate::{
+    error::MadokamiError,
+    helper::{extract_manga_title, url_encode},
+    listing::series_manga,
+    net::{base_url, fetch_html},
+    series::fetch_series_info,
+};
+
+/// How many results are checked against their series page when filtering by
+/// completion. Each unknown status costs a request, so this is kept well under
+/// the default rate limit; further results with an unknown status are left
+/// out.
+const MAX_STATUS_CHECKS: usize = 10;
+
+/// Top-level directory a search can be restricted to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    Manga,
+    Raws,
+    Novels,
+}
+
+impl Scope {
+    fn prefix(&self) -> Option<&'static str> {
+        match self {
+            Self::All => None,
+            Self::Manga => Some("/Manga/"),
+            Self::Raws => Some("/Raws/"),
+            Self::Novels => Some("/Novels/"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// The order the site returns results in.
+    Relevance,
+    Name,
+    Date,
+}
+
+pub struct SearchOptions {
+    pub query: String,
+    pub scope: Scope,
+    pub completed_only: bool,
+    pub sort: SortBy,
+    pub ascending: bool,
+}
+
+impl SearchOptions {
+    /// Reads the search options from the filters declared in `filters.json`.
+    /// Returns `None` if there's no title to search for.
+    pub fn from_filters(filters: Vec<Filter>) -> Option<Self> {
+        let mut options = Self {
+            query: String::new(),
+            scope: Scope::All,
+            completed_only: false,
+            sort: SortBy::Relevance,
+            ascending: false,
+        };
+        for filter in filters {
+            match filter.kind {
+                FilterType::Title => {
+                    options.query = filter.value.as_string().map(|s| s.read()).unwrap_or_default();
+                }
+                FilterType::Select if filter.name == "Scope" => {
+                    options.scope = match filter.value.as_int().unwrap_or(0) {
+                        1 => Scope::Manga,
+                        2 => Scope::Raws,
+                        3 => Scope::Novels,
+                        _ => Scope::All,
+                    };
+                }
+                FilterType::Check if filter.name == "Completed only" => {
+                    options.completed_only = filter.value.as_int().unwrap_or(0) > 0;
+                }
+                FilterType::Sort => {
+                    let Ok(value) = filter.value.as_object() else {
+                        continue;
+                    };
+                    options.sort = match value.get("index").as_int().unwrap_or(0) {
+                        1 => SortBy::Name,
+                        2 => SortBy::Date,
+                        _ => SortBy::Relevance,
+                    };
+                    options.ascending = value.get("ascending").as_bool().unwrap_or(false);
+                }
+                _ => continue,
+            }
+        }
+        if options.query.trim().is_empty() {
+            None
+        } else {
+            Some(options)
+        }
+    }
+}
+
+struct SearchResult {
+    path: String,
+    date: f64,
+    author: String,
+    status: MangaStatus,
+    tags: Vec<String>,
+}
+
+impl SearchResult {
+    /// Fills in whatever the search table didn't provide from the series page.
+    fn enrich(&mut self) -> core::result::Result<(), MadokamiError> {
+        let info = fetch_series_info(&self.path)?;
+        if self.author.is_empty() {
+            self.author = info.authors.join(", ");
+        }
+        if self.status == MangaStatus::Unknown {
+            self.status = info.status;
+        }
+        if self.tags.is_empty() {
+            self.tags = info.genres;
+        }
+        Ok(())
+    }
+
+    fn into_manga(self, base_url: &str) -> Manga {
+        Manga {
+            author: self.author,
+            status: self.status,
+            categories: self.tags,
+            ..series_manga(self.path, base_url)
+        }
+    }
+}
+
+/// Column indices (1-based, for `nth-child`) of the optional columns in the
+/// search results table, found by their header text.
+#[derive(Default)]
+struct Columns {
+    date: Option<usize>,
+    author: Option<usize>,
+    status: Option<usize>,
+    tags: Option<usize>,
+}
+
+impl Columns {
+    fn parse(html: &Node) -> Self {
+        let mut columns = Self::default();
+        for (idx, header) in html
+            .select("div.container table thead th")
+            .array()
+            .enumerate()
+        {
+            let Ok(header) = header.as_node() else {
+                continue;
+            };
+            let name = header.text().read().trim().to_lowercase();
+            let slot = match name.as_str() {
+                "date" | "last modified" | "updated" | "added" => &mut columns.date,
+                "author" | "authors" | "artist" => &mut columns.author,
+                "status" | "completed" => &mut columns.status,
+                "tags" | "genres" => &mut columns.tags,
+                _ => continue,
+            };
+            *slot = Some(idx + 1);
+        }
+        columns
+    }
+}
+
+fn cell(row: &Node, column: Option<usize>) -> Option<Node> {
+    column.map(|idx| row.select(format!("td:nth-child({})", idx)))
+}
+
+fn parse_status(text: &str) -> MangaStatus {
+    match text.trim().to_lowercase().as_str() {
+        "yes" | "completed" | "complete" => MangaStatus::Completed,
+        "ongoing" => MangaStatus::Ongoing,
+        _ => MangaStatus::Unknown,
+    }
+}
+
+/// How many of the top results get their details filled in from the series
+/// page. Pages are cached, so repeated searches don't refetch them.
+fn enrich_count() -> usize {
+    defaults_get("enrichResults")
+        .and_then(|v| v.as_int())
+        .unwrap_or(0)
+        .max(0) as usize
+}
+
+/// Runs a search, then applies the scope, completion and sort options to the
+/// results, which the site's search doesn't support itself.
+pub fn search(options: &SearchOptions) -> Result<Vec<Manga>> {
+    let html = fetch_html(&format!("/search?q={}", url_encode(&options.query)))?;
+
+    let columns = Columns::parse(&html);
+    let mut results = Vec::new();
+    for row in html.select("div.container table tbody tr").array() {
+        let Ok(row) = row.as_node() else {
+            continue;
+        };
+        let path = row.select("td:nth-child(1) a:nth-child(1)").attr("href").read();
+        if path.trim().is_empty() {
+            continue;
+        }
+        if let Some(prefix) = options.scope.prefix() {
+            if !path.starts_with(prefix) {
+                continue;
+            }
+        }
+        results.push(SearchResult {
+            path,
+            date: cell(&row, columns.date.or(Some(3)))
+                .map(|n| n.text().as_date("yyyy-MM-dd HH:mm", None, None))
+                .unwrap_or(-1.0),
+            author: cell(&row, columns.author)
+                .map(|n| String::from(n.text().read().trim()))
+                .unwrap_or_default(),
+            status: cell(&row, columns.status)
+                .map(|n| parse_status(&n.text().read()))
+                .unwrap_or(MangaStatus::Unknown),
+            tags: cell(&row, columns.tags)
+                .map(|n| {
+                    n.select("a")
+                        .array()
+                        .filter_map(|a| a.as_node().ok().map(|a| a.text().read()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+    }
+
+    if options.completed_only {
+        let mut checks = 0;
+        let mut completed = Vec::new();
+        for mut result in results {
+            if result.status == MangaStatus::Unknown {
+                if checks == MAX_STATUS_CHECKS {
+                    continue;
+                }
+                checks += 1;
+                match result.enrich() {
+                    Ok(()) | Err(MadokamiError::NotFound) => {}
+                    Err(error) => return Err(error.into()),
+                }
+            }
+            if result.status == MangaStatus::Completed {
+                completed.push(result);
+            }
+        }
+        results = completed;
+    }
+
+    match options.sort {
+        SortBy::Relevance => {}
+        SortBy::Name => {
+            results.sort_by_cached_key(|r| extract_manga_title(&r.path).to_lowercase());
+            if !options.ascending {
+                results.reverse();
+            }
+        }
+        SortBy::Date => {
+            results.sort_by(|a, b| a.date.total_cmp(&b.date));
+            if !options.ascending {
+                results.reverse();
+            }
+        }
+    }
+
+    // Enriching is best effort; a failure just leaves the entry as it was.
+    for result in results.iter_mut().take(enrich_count()) {
+        result.enrich().ok();
+    }
+
+    let base_url = base_url();
+    Ok(results
+        .into_iter()
+        .map(|r| r.into_manga(&base_url))
+        .collect())
+}
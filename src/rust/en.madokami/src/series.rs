@@ -0,0 +1,137 @@
+use aidoku::{
+    prelude::format,
+    std::{current_date, html::Node, String, Vec},
+    MangaStatus,
+};
+
+use crate::{
+    cache::LruCache,
+    cell::SessionCell,
+    error::MadokamiError,
+    helper::{clean_description, get_parent_path, parse_tracker_link, TrackerLink},
+    net::fetch_html,
+};
+
+/// Metadata shown in the info box of a series page.
+#[derive(Clone)]
+pub struct SeriesInfo {
+    pub cover: String,
+    pub authors: Vec<String>,
+    pub genres: Vec<String>,
+    pub status: MangaStatus,
+    pub description: String,
+    /// Other names the series is known by.
+    pub alt_titles: Vec<String>,
+    /// Entries for the series on tracking sites, kept so the library can be
+    /// matched against trackers and other sources.
+    pub trackers: Vec<TrackerLink>,
+}
+
+impl SeriesInfo {
+    /// The alternative titles and tracker IDs, formatted to be appended to
+    /// the description. Empty if there are neither.
+    pub fn matching_metadata(&self) -> String {
+        let mut lines = Vec::new();
+        if !self.alt_titles.is_empty() {
+            lines.push(format!("Alternative titles: {}", self.alt_titles.join("; ")));
+        }
+        for link in &self.trackers {
+            lines.push(format!("{}: {}", link.tracker, link.id));
+        }
+        lines.join("\n")
+    }
+}
+
+/// How many series pages are kept in memory.
+const CACHE_CAPACITY: usize = 64;
+
+/// How long a cached series page stays valid, in seconds.
+const CACHE_TTL: f64 = 10.0 * 60.0;
+
+/// Parsed series pages, keyed by the series path.
+static SERIES_CACHE: SessionCell<LruCache<SeriesInfo>> =
+    SessionCell::new(LruCache::new(CACHE_CAPACITY, CACHE_TTL));
+
+fn node_texts(html: &Node, selector: &str) -> Vec<String> {
+    html.select(selector)
+        .array()
+        .filter_map(|n| n.as_node().ok().map(|node| node.text().read()))
+        .collect()
+}
+
+/// Parses the metadata from a series page.
+pub fn parse_series_info(html: &Node) -> SeriesInfo {
+    let description = {
+        let og_desc = html
+            .select("meta[property=\"og:description\"]")
+            .attr("content")
+            .read();
+        let desc = if !og_desc.is_empty() {
+            og_desc
+        } else {
+            html.select("meta[name=\"description\"]")
+                .attr("content")
+                .read()
+        };
+        clean_description(&desc)
+    };
+
+    SeriesInfo {
+        cover: html
+            .select("div.manga-info img[itemprop=\"image\"]")
+            .attr("src")
+            .read(),
+        authors: node_texts(html, "a[itemprop=\"author\"]"),
+        genres: node_texts(html, "div.genres a.tag"),
+        status: if html.select("span.scanstatus").text().read() == "Yes" {
+            MangaStatus::Completed
+        } else {
+            MangaStatus::Unknown
+        },
+        description,
+        alt_titles: node_texts(html, "span.altnames span, [itemprop=\"alternativeHeadline\"]")
+            .into_iter()
+            .map(|title| String::from(title.trim().trim_end_matches(',')))
+            .filter(|title| !title.is_empty())
+            .collect(),
+        trackers: html
+            .select("div.manga-info a[href]")
+            .array()
+            .filter_map(|n| n.as_node().ok())
+            .filter_map(|node| parse_tracker_link(&node.attr("href").read()))
+            .fold(Vec::new(), |mut links: Vec<TrackerLink>, link| {
+                if !links.iter().any(|l| l.tracker == link.tracker) {
+                    links.push(link);
+                }
+                links
+            }),
+    }
+}
+
+/// Stores the metadata of a series page that was fetched for another reason,
+/// such as listing its chapters. Pages of subdirectories are ignored, since
+/// the series metadata lives on the page above them.
+pub fn remember_series_info(path: &str, html: &Node) {
+    if let Some(page) = get_parent_path(path) {
+        if page == path.trim_end_matches('/') {
+            let info = parse_series_info(html);
+            SERIES_CACHE.with(|cache| cache.insert(page, info, current_date()));
+        }
+    }
+}
+
+/// Fetches the metadata for the series that `path` belongs to. Files and
+/// special subdirectories (`!Extras`, VIZBIG editions) resolve to the series
+/// page above them.
+///
+/// Pages are cached for a few minutes, so details, chapter lists and search
+/// enrichment share a single request per series.
+pub fn fetch_series_info(path: &str) -> Result<SeriesInfo, MadokamiError> {
+    let page = get_parent_path(path).ok_or(MadokamiError::NotFound)?;
+    if let Some(info) = SERIES_CACHE.with(|cache| cache.get(&page, current_date())) {
+        return Ok(info);
+    }
+    let info = parse_series_info(&fetch_html(&page)?);
+    SERIES_CACHE.with(|cache| cache.insert(page, info.clone(), current_date()));
+    Ok(info)
+}
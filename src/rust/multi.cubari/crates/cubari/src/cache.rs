@@ -0,0 +1,62 @@
+use aidoku::std::{current_date, ObjectRef, String, Vec};
+use alloc::string::ToString;
+
+use crate::{cell::StaticCell, helper::Slug};
+
+/// How many series API responses are kept in memory.
+const CAPACITY: usize = 32;
+
+/// How long a cached API response stays valid, in seconds.
+const TTL: f64 = 10.0 * 60.0;
+
+struct Entry {
+	/// The series' `{source}/{slug}` id.
+	id: String,
+	json: ObjectRef,
+	/// When the response was fetched, in seconds since the epoch.
+	fetched_at: f64,
+}
+
+/// Series API responses, most recently used first. Opening a series requests
+/// its details, chapters and pages one after another, all from the same
+/// response, and the list is short enough that a linear scan is fine.
+static ENTRIES: StaticCell<Vec<Entry>> = StaticCell::new(Vec::new());
+
+/// Returns the cached response for a series, unless it has expired.
+pub fn get(slug: &Slug) -> Option<ObjectRef> {
+	let id = slug.to_string();
+	let now = current_date();
+	ENTRIES.with(|entries| {
+		let idx = entries.iter().position(|entry| entry.id == id)?;
+		let entry = entries.remove(idx);
+		if now - entry.fetched_at > TTL {
+			return None;
+		}
+		let json = entry.json.clone();
+		entries.insert(0, entry);
+		Some(json)
+	})
+}
+
+/// Stores the response for a series, dropping the least recently used one
+/// once the cache is full.
+pub fn insert(slug: &Slug, json: ObjectRef) {
+	let id = slug.to_string();
+	let fetched_at = current_date();
+	ENTRIES.with(|entries| {
+		entries.retain(|entry| entry.id != id);
+		entries.truncate(CAPACITY - 1);
+		entries.insert(
+			0,
+			Entry {
+				id,
+				json,
+				fetched_at,
+			},
+		);
+	})
+}
+
+pub fn clear() {
+	ENTRIES.with(|entries| entries.clear());
+}
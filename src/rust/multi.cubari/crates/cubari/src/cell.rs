@@ -0,0 +1,21 @@
+use core::cell::RefCell;
+
+/// A `RefCell` that can be kept in a `static`, for state the source holds
+/// between calls, like the series cache.
+pub struct StaticCell<T>(RefCell<T>);
+
+// SAFETY: a Cubari source instance is a single threaded wasm module, so the
+// cell is never reached from two threads.
+unsafe impl<T> Sync for StaticCell<T> {}
+
+impl<T> StaticCell<T> {
+	pub const fn new(value: T) -> Self {
+		Self(RefCell::new(value))
+	}
+
+	/// Runs `f` with the value borrowed mutably. The borrow ends when `f`
+	/// returns, so `f` mustn't reach the same cell again.
+	pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+		f(&mut self.0.borrow_mut())
+	}
+}
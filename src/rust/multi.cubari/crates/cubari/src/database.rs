@@ -1,14 +1,75 @@
 use aidoku::{
 	error::Result,
 	prelude::format,
-	std::{defaults::*, ArrayRef, ObjectRef, String, StringRef, Vec},
-	Manga,
+	std::{current_date, defaults::*, ArrayRef, ObjectRef, String, StringRef, ValueRef, Vec},
+	Manga, MangaContentRating, MangaStatus, MangaViewer,
 };
 
+use crate::helper::fold_text;
+
+/// A series in the history, with when it was first found and last opened.
+pub struct HistoryEntry {
+	pub manga: Manga,
+	/// Seconds since the epoch.
+	pub added: f64,
+	/// Seconds since the epoch.
+	pub last_seen: f64,
+}
+
+/// How the History listing and history search are ordered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HistorySort {
+	LastSeen,
+	Added,
+	Title,
+}
+
+impl HistorySort {
+	pub fn from_settings() -> Self {
+		match defaults_get("historySort")
+			.as_string()
+			.map(|s| s.read())
+			.unwrap_or_default()
+			.as_str()
+		{
+			"added" => Self::Added,
+			"title" => Self::Title,
+			_ => Self::LastSeen,
+		}
+	}
+}
+
+/// Version of the `history.{id}` objects. Version 1 only stored the cover
+/// and title; version 2 stores the whole series and when it was seen.
+const SCHEMA_VERSION: i64 = 2;
+
+/// Reports the result of the last change to the history in settings.
+pub fn set_status(status: &str) {
+	defaults_set("history.status", StringRef::from(status).0);
+}
+
 pub fn initialize() {
 	if defaults_get("history.series").is_none() {
 		defaults_set("history.series", ArrayRef::new().0);
+		defaults_set("history.version", ValueRef::from(SCHEMA_VERSION));
+	} else {
+		if defaults_get("history.version").as_int().unwrap_or(1) < SCHEMA_VERSION {
+			migrate().ok();
+		}
+		prune().ok();
+	}
+}
+
+/// Rewrites every entry in the current schema, filling in what older
+/// versions didn't store.
+fn migrate() -> Result<()> {
+	for id in series_list()? {
+		if let Ok(entry) = get_entry(&id) {
+			write_entry(&entry);
+		}
 	}
+	defaults_set("history.version", ValueRef::from(SCHEMA_VERSION));
+	Ok(())
 }
 
 pub fn series_list() -> Result<Vec<String>> {
@@ -20,55 +81,208 @@ pub fn series_list() -> Result<Vec<String>> {
 	Ok(result)
 }
 
-pub fn add_or_update_manga(manga: &Manga) -> Result<()> {
+fn series_url(id: &str) -> String {
+	format!("https://cubari.moe/read/{id}/")
+}
+
+fn write_entry(entry: &HistoryEntry) {
+	let manga = &entry.manga;
+	let mut obj = ObjectRef::new();
+	obj.set("cover", StringRef::from(&manga.cover).0);
+	obj.set("title", StringRef::from(&manga.title).0);
+	obj.set("author", StringRef::from(&manga.author).0);
+	obj.set("artist", StringRef::from(&manga.artist).0);
+	obj.set("description", StringRef::from(&manga.description).0);
+	obj.set("url", StringRef::from(&manga.url).0);
+	obj.set("nsfw", ValueRef::from(manga.nsfw as i32));
+	obj.set("added", ValueRef::from(entry.added));
+	obj.set("lastSeen", ValueRef::from(entry.last_seen));
+	defaults_set(&format!("history.{}", manga.id), obj.0);
+}
+
+/// Stores a series in the history. The time it was added is kept, and the
+/// last seen time only ever moves forward.
+fn save_manga(manga: Manga, last_seen: f64) -> Result<bool> {
+	// Add manga in index if it doesn't already exist
+	let is_new = !series_list().unwrap_or_default().contains(&manga.id);
+	if is_new {
+		let mut series = defaults_get("history.series").as_array()?;
+		series.insert(StringRef::from(&manga.id).0);
+		defaults_set("history.series", series.0);
+	}
+
+	let (added, previously_seen) = get_entry(&manga.id)
+		.map(|entry| (entry.added, entry.last_seen))
+		.unwrap_or((current_date(), 0.0));
+	write_entry(&HistoryEntry {
+		manga,
+		added,
+		last_seen: last_seen.max(previously_seen),
+	});
+	Ok(is_new)
+}
+
+/// Saves a found series to the history, unless disabled in settings.
+/// Returns whether the series wasn't in the history before.
+pub fn add_or_update_manga(manga: &Manga) -> Result<bool> {
 	if aidoku::std::defaults::defaults_get("saveSeries")
 		.as_bool()
 		.unwrap_or(true)
 	{
-		let key = String::from(&manga.id);
-
-		// Add manga in index if it doesn't already exist
-		if !series_list().unwrap_or_default().contains(&key) {
-			let mut series = defaults_get("history.series").as_array()?;
-			series.insert(StringRef::from(&key).0);
-			defaults_set("history.series", series.0);
-		}
+		save_manga(
+			Manga {
+				id: manga.id.clone(),
+				cover: manga.cover.clone(),
+				title: manga.title.clone(),
+				author: manga.author.clone(),
+				artist: manga.artist.clone(),
+				description: manga.description.clone(),
+				url: manga.url.clone(),
+				categories: Vec::new(),
+				status: manga.status,
+				viewer: manga.viewer,
+				nsfw: manga.nsfw,
+			},
+			current_date(),
+		)
+	} else {
+		Ok(false)
+	}
+}
 
-		// Update manga in index
-		let mut obj = ObjectRef::new();
-		obj.set("cover", StringRef::from(&manga.cover).0);
-		obj.set("title", StringRef::from(&manga.title).0);
-		defaults_set(&format!("history.{key}"), obj.0);
+/// Merges a series from remote storage into the history. Local data wins,
+/// since it comes straight from the Cubari API, while the later of the two
+/// last seen times is kept.
+pub fn merge_manga(id: &str, title: &str, cover: &str, last_seen: f64) -> Result<()> {
+	let mut manga = get_manga(id).unwrap_or_else(|_| empty_manga(id));
+	if manga.title.is_empty() {
+		manga.title = String::from(title);
 	}
+	if manga.cover.is_empty() {
+		manga.cover = String::from(cover);
+	}
+	save_manga(manga, last_seen)?;
 	Ok(())
 }
 
-pub fn get_manga<T: AsRef<str>>(id: T) -> Result<Manga> {
-	let id = id.as_ref();
-	let obj = defaults_get(&format!("history.{id}")).as_object()?;
-	let cover = obj.get("cover").as_string()?.read();
-	let title = obj.get("title").as_string()?.read();
-	Ok(Manga {
+fn empty_manga(id: &str) -> Manga {
+	Manga {
 		id: String::from(id),
-		cover,
-		title,
+		cover: String::new(),
+		title: String::new(),
 		author: String::new(),
 		artist: String::new(),
 		description: String::new(),
-		url: String::new(),
+		url: series_url(id),
 		categories: Vec::new(),
-		status: aidoku::MangaStatus::Unknown,
-		viewer: aidoku::MangaViewer::Rtl,
-		nsfw: aidoku::MangaContentRating::Safe,
+		status: MangaStatus::Unknown,
+		viewer: MangaViewer::Rtl,
+		nsfw: if id.starts_with("nhentai/") {
+			MangaContentRating::Nsfw
+		} else {
+			MangaContentRating::Safe
+		},
+	}
+}
+
+/// Reads a history entry. Fields that older schema versions didn't store
+/// are filled in with defaults.
+pub fn get_entry<T: AsRef<str>>(id: T) -> Result<HistoryEntry> {
+	let id = id.as_ref();
+	let obj = defaults_get(&format!("history.{id}")).as_object()?;
+	let string = |key: &str| obj.get(key).as_string().map(|s| s.read()).ok();
+	let mut manga = empty_manga(id);
+	manga.cover = obj.get("cover").as_string()?.read();
+	manga.title = obj.get("title").as_string()?.read();
+	manga.author = string("author").unwrap_or_default();
+	manga.artist = string("artist").unwrap_or_default();
+	manga.description = string("description").unwrap_or_default();
+	manga.url = string("url")
+		.filter(|url| !url.is_empty())
+		.unwrap_or(manga.url);
+	manga.nsfw = match obj.get("nsfw").as_int() {
+		Ok(1) => MangaContentRating::Suggestive,
+		Ok(2) => MangaContentRating::Nsfw,
+		Ok(_) => MangaContentRating::Safe,
+		Err(_) => manga.nsfw,
+	};
+	// Entries saved before timestamps were recorded have neither.
+	let added = obj.get("added").as_float().unwrap_or(0.0);
+	Ok(HistoryEntry {
+		manga,
+		added,
+		last_seen: obj.get("lastSeen").as_float().unwrap_or(added),
 	})
 }
 
-pub fn delete_all_manga() -> Result<()> {
-	let series = defaults_get("history.series").as_array()?;
-	for item in series {
-		let id = item.as_string()?.read();
-		defaults_set(&format!("history.{id}"), ObjectRef::new().0);
+pub fn get_manga<T: AsRef<str>>(id: T) -> Result<Manga> {
+	get_entry(id).map(|entry| entry.manga)
+}
+
+/// Series in the history whose title contains `query`, ignoring case and
+/// accents, in the order chosen in settings. An empty query matches all.
+pub fn search(query: &str) -> Vec<Manga> {
+	let query = fold_text(query.trim());
+	let mut entries = series_list()
+		.unwrap_or_default()
+		.iter()
+		.filter_map(|id| get_entry(id).ok())
+		.filter(|entry| fold_text(&entry.manga.title).contains(&query))
+		.collect::<Vec<_>>();
+	match HistorySort::from_settings() {
+		HistorySort::LastSeen => entries.sort_by(|a, b| b.last_seen.total_cmp(&a.last_seen)),
+		HistorySort::Added => entries.sort_by(|a, b| b.added.total_cmp(&a.added)),
+		HistorySort::Title => entries.sort_by_cached_key(|entry| fold_text(&entry.manga.title)),
+	}
+	entries.into_iter().map(|entry| entry.manga).collect()
+}
+
+/// Drops a stored object. There's no way to remove a key, so it's nulled.
+fn clear_entry(id: &str) {
+	defaults_set(&format!("history.{id}"), ValueRef::null());
+}
+
+fn set_series_list(ids: &[String]) {
+	let mut series = ArrayRef::new();
+	for id in ids {
+		series.insert(StringRef::from(id).0);
+	}
+	defaults_set("history.series", series.0);
+}
+
+/// Removes index entries whose object is missing or empty, as left behind
+/// by older versions of `delete_all_manga`. Returns how many were removed.
+fn prune() -> Result<usize> {
+	let ids = series_list()?;
+	let (kept, pruned): (Vec<_>, Vec<_>) = ids.into_iter().partition(|id| get_entry(id).is_ok());
+	if !pruned.is_empty() {
+		for id in &pruned {
+			clear_entry(id);
+		}
+		set_series_list(&kept);
+	}
+	Ok(pruned.len())
+}
+
+/// Removes a single series from the history. Returns whether it was there.
+pub fn remove_manga(id: &str) -> Result<bool> {
+	let mut ids = series_list()?;
+	let count = ids.len();
+	ids.retain(|series| series != id);
+	clear_entry(id);
+	if ids.len() == count {
+		return Ok(false);
+	}
+	set_series_list(&ids);
+	Ok(true)
+}
+
+/// Deletes the whole history. Returns how many series were removed.
+pub fn delete_all_manga() -> Result<usize> {
+	let ids = series_list()?;
+	for id in &ids {
+		clear_entry(id);
 	}
 	defaults_set("history.series", ArrayRef::new().0);
-	Ok(())
+	Ok(ids.len())
 }
@@ -0,0 +1,59 @@
+use aidoku::error::{AidokuError, AidokuErrorKind, ValueCastError};
+use core::fmt;
+
+/// Why a Cubari API request failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubariError {
+	/// The id isn't a `{source}/{slug}` pair, so there's nothing to request.
+	InvalidSlug,
+	/// The request didn't get a response at all.
+	Network,
+	/// The response wasn't the JSON that was expected.
+	InvalidJson,
+	/// The series doesn't exist (404), or the source can't find it.
+	NotFound,
+	/// Any other unsuccessful status.
+	UnexpectedStatus(i32),
+}
+
+impl CubariError {
+	/// Maps an HTTP status to an error, or `None` for a successful response.
+	pub fn from_status(status: i32) -> Option<Self> {
+		match status {
+			200..=299 => None,
+			// The request never completed.
+			i32::MIN..=0 => Some(Self::Network),
+			404 | 410 => Some(Self::NotFound),
+			_ => Some(Self::UnexpectedStatus(status)),
+		}
+	}
+}
+
+impl fmt::Display for CubariError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidSlug => write!(f, "invalid slug"),
+			Self::Network => write!(f, "network error"),
+			Self::InvalidJson => write!(f, "response isn't valid JSON"),
+			Self::NotFound => write!(f, "not found"),
+			Self::UnexpectedStatus(status) => write!(f, "unexpected status ({status})"),
+		}
+	}
+}
+
+impl From<CubariError> for AidokuError {
+	/// A slug that doesn't parse is reported like any other id that isn't the
+	/// expected string. Requests without a usable answer have no kind of
+	/// their own, so they fall back to `Unimplemented`.
+	fn from(error: CubariError) -> Self {
+		let reason = match error {
+			CubariError::InvalidSlug => AidokuErrorKind::ValueCast(ValueCastError::NotString),
+			CubariError::InvalidJson => AidokuErrorKind::JsonParseError,
+			CubariError::NotFound => AidokuErrorKind::DefaultNotFound,
+			CubariError::Network | CubariError::UnexpectedStatus(_) => {
+				AidokuErrorKind::Unimplemented
+			}
+		};
+		AidokuError { reason }
+	}
+}
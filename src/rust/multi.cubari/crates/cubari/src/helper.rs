@@ -1,4 +1,5 @@
 use aidoku::{
+	prelude::format,
 	std::{String, Vec},
 	Manga, MangaContentRating, MangaStatus, MangaViewer,
 };
@@ -23,6 +24,62 @@ pub fn urlencode(string: String) -> String {
 	String::from_utf8(result).unwrap_or_default()
 }
 
+/// Lowercases text and strips accents from Latin letters, so titles can be
+/// matched regardless of how they were typed.
+pub fn fold_text<T: AsRef<str>>(text: T) -> String {
+	const ACCENTED: [(&str, char); 15] = [
+		("àáâãäåāăą", 'a'),
+		("çćĉċč", 'c'),
+		("ďđ", 'd'),
+		("èéêëēĕėęě", 'e'),
+		("ĝğġģ", 'g'),
+		("ìíîïĩīĭįı", 'i'),
+		("ĺļľŀł", 'l'),
+		("ñńņňŉ", 'n'),
+		("òóôõöøōŏő", 'o'),
+		("ŕŗř", 'r'),
+		("śŝşš", 's'),
+		("ţťŧ", 't'),
+		("ùúûüũūŭůűų", 'u'),
+		("ýÿŷ", 'y'),
+		("źżž", 'z'),
+	];
+	text.as_ref()
+		.chars()
+		.flat_map(char::to_lowercase)
+		.map(|c| {
+			if c.is_ascii() {
+				return c;
+			}
+			ACCENTED
+				.iter()
+				.find(|(accented, _)| accented.contains(c))
+				.map(|(_, plain)| *plain)
+				.unwrap_or(c)
+		})
+		.collect()
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+pub fn json_string<T: AsRef<str>>(string: T) -> String {
+	let string = string.as_ref();
+	let mut result = String::with_capacity(string.len() + 2);
+	result.push('"');
+	for c in string.chars() {
+		match c {
+			'"' => result.push_str("\\\""),
+			'\\' => result.push_str("\\\\"),
+			'\n' => result.push_str("\\n"),
+			'\r' => result.push_str("\\r"),
+			'\t' => result.push_str("\\t"),
+			c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+	result
+}
+
 pub fn cubari_guide() -> Manga {
 	Manga {
 		id: String::from("aidoku/guide"),
@@ -46,3 +103,17 @@ pub fn img_url_handler(url: String) -> String {
 		url
 	}
 }
+
+/// Serialises a series the way cubari.moe stores it in remoteStorage, with
+/// `timestamp` (in seconds) as the time it was last read.
+pub fn series_json(slug: &Slug, manga: &Manga, timestamp: f64) -> String {
+	format!(
+		r#"{{"slug":{},"source":{},"title":{},"coverUrl":{},"url":{},"timestamp":{},"chapters":[],"pinned":false}}"#,
+		json_string(&slug.id),
+		json_string(&slug.source),
+		json_string(&manga.title),
+		json_string(&manga.cover),
+		json_string(&manga.url),
+		(timestamp * 1000.0) as i64,
+	)
+}
@@ -1,52 +1,65 @@
 #![no_std]
 extern crate alloc;
+mod cache;
+mod cell;
 mod database;
+mod error;
 mod helper;
+mod remotestorage;
+mod transfer;
 use aidoku::{
-	error::{AidokuError, AidokuErrorKind, Result},
+	error::Result,
 	prelude::*,
 	std::{
 		net::{HttpMethod, Request},
 		ArrayRef, Kind, ObjectRef, String, ValueRef, Vec,
 	},
-	Chapter, DeepLink, Filter, FilterType, Manga, MangaContentRating, MangaPageResult, MangaStatus,
-	MangaViewer, Page,
+	Chapter, DeepLink, Filter, FilterType, Listing, Manga, MangaContentRating, MangaPageResult,
+	MangaStatus, MangaViewer, Page,
 };
-use alloc::vec;
+use alloc::{string::ToString, vec};
+use error::CubariError;
 use float_ord::FloatOrd;
 use helper::*;
+use remotestorage::RemoteStorage;
 
-static mut CACHED_SLUG: Option<String> = None;
-static mut CACHED_JSON: Option<ObjectRef> = None;
-fn cache_api_request<T: AsRef<str>>(slug: T) -> Result<()> {
-	let slug = slug.as_ref();
-	unsafe {
-		if CACHED_JSON.is_some() && slug == CACHED_SLUG.clone().unwrap() {
-			return Ok(());
-		}
-
-		let fragments = slug.split('/').collect::<Vec<_>>();
+/// Requests a Cubari API endpoint and parses the response as JSON.
+fn fetch_json(url: &str) -> core::result::Result<ValueRef, CubariError> {
+	let request = Request::new(url, HttpMethod::Get);
+	request.send();
+	if let Some(error) = CubariError::from_status(request.status_code()) {
+		return Err(error);
+	}
+	let json = request.json();
+	match json.kind() {
+		Kind::Object | Kind::Array => Ok(json),
+		_ => Err(CubariError::InvalidJson),
+	}
+}
 
-		CACHED_SLUG = Some(String::from(slug));
-		match Request::new(
-			&format!(
-				"https://cubari.moe/read/api/{}/series/{}/",
-				fragments[0], fragments[1]
-			),
-			HttpMethod::Get,
-		)
-		.json()
-		.as_object()
-		{
-			Ok(obj) => {
-				CACHED_JSON = Some(obj);
-				Ok(())
-			}
-			Err(_) => Err(AidokuError {
-				reason: AidokuErrorKind::Unimplemented,
-			}),
+/// Fetches the series JSON for a slug, using the cache unless `refresh` is
+/// set. Only successful responses are cached, so a failed fetch is retried
+/// the next time.
+fn cache_api_request(slug: &Slug, refresh: bool) -> core::result::Result<ObjectRef, CubariError> {
+	if !refresh {
+		if let Some(json) = cache::get(slug) {
+			return Ok(json);
 		}
 	}
+
+	let json = fetch_json(&format!(
+		"https://cubari.moe/read/api/{}/series/{}/",
+		slug.source, slug.id
+	))?
+	.as_object()
+	.map_err(|_| CubariError::InvalidJson)?;
+	cache::insert(slug, json.clone());
+	Ok(json)
+}
+
+/// Parses a series id, which is always a Cubari slug.
+fn parse_id(id: &str) -> core::result::Result<Slug, CubariError> {
+	Slug::parse(id).ok_or(CubariError::InvalidSlug)
 }
 
 #[no_mangle]
@@ -65,53 +78,73 @@ fn get_manga_list(filters: Vec<Filter>, _: i32) -> Result<MangaPageResult> {
 			_ => continue,
 		}
 	}
-	let slug = url_to_slug(query);
-	// Assume it's a title search
-	if !slug.contains('/') {
-		let series_list = database::series_list().unwrap_or_default();
-		let mut manga: Vec<Manga> = Vec::new();
-		if !series_list.is_empty() {
-			for series in series_list {
-				match database::get_manga(series) {
-					Ok(res) => {
-						if res.title.to_lowercase().contains(&slug) {
-							manga.push(res);
-						} else {
-							continue;
-						}
-					}
-					Err(_) => continue,
-				}
+	// `remove:<slug or URL>` removes a series from the history.
+	if let Some(target) = query.trim().strip_prefix("remove:") {
+		let slug = Slug::from_url(target.trim()).ok_or(CubariError::InvalidSlug)?;
+		if database::remove_manga(&slug.to_string())? {
+			if let Some(remote) = RemoteStorage::from_settings() {
+				remote.remove(&slug).ok();
 			}
+			database::set_status(&format!("Removed {slug}"));
 		}
-		if aidoku::std::defaults::defaults_get("showHelp")
-			.as_bool()
-			.unwrap_or(true)
-		{
-			manga.push(cubari_guide())
-		}
-		Ok(MangaPageResult {
-			manga,
+		return Ok(MangaPageResult {
+			manga: search_history(""),
 			has_more: false,
-		})
-	} else {
-		Ok(MangaPageResult {
-			manga: match get_manga_details(slug) {
+		});
+	}
+	match Slug::from_url(&query) {
+		Some(slug) => Ok(MangaPageResult {
+			manga: match get_manga_details(slug.to_string()) {
 				Ok(manga) => vec![manga],
 				Err(_) => Vec::new(),
 			},
 			has_more: false,
-		})
+		}),
+		// Assume it's a title search
+		None => {
+			let mut manga = search_history(&query);
+			if aidoku::std::defaults::defaults_get("showHelp")
+				.as_bool()
+				.unwrap_or(true)
+			{
+				manga.push(cubari_guide())
+			}
+			Ok(MangaPageResult {
+				manga,
+				has_more: false,
+			})
+		}
 	}
 }
 
+/// Searches the history, pulling the remote history first if it's set up.
+fn search_history(query: &str) -> Vec<Manga> {
+	if let Some(remote) = RemoteStorage::from_settings() {
+		remote.pull().ok();
+	}
+	database::search(query)
+}
+
+#[get_manga_listing]
+fn get_manga_listing(listing: Listing, _: i32) -> Result<MangaPageResult> {
+	Ok(MangaPageResult {
+		manga: if listing.name == "History" {
+			search_history("")
+		} else {
+			Vec::new()
+		},
+		has_more: false,
+	})
+}
+
 #[get_manga_details]
 fn get_manga_details(id: String) -> Result<Manga> {
 	if id == "aidoku/guide" {
 		Ok(cubari_guide())
 	} else {
-		cache_api_request(&id)?;
-		let json = unsafe { CACHED_JSON.clone().unwrap() };
+		// Details are loaded when a series is opened or pulled to refresh, so
+		// always fetch them fresh; the chapter list then reuses the response.
+		let json = cache_api_request(&parse_id(&id)?, true)?;
 		let manga = Manga {
 			url: format!("https://cubari.moe/read/{}", &id),
 			nsfw: if id.contains("nhentai") {
@@ -129,7 +162,11 @@ fn get_manga_details(id: String) -> Result<Manga> {
 			status: MangaStatus::Unknown,
 			viewer: MangaViewer::Rtl,
 		};
-		database::add_or_update_manga(&manga).ok();
+		if database::add_or_update_manga(&manga).unwrap_or(false) {
+			if let Some(remote) = RemoteStorage::from_settings() {
+				remote.push(&manga).ok();
+			}
+		}
 		Ok(manga)
 	}
 }
@@ -148,8 +185,7 @@ fn get_chapter_list(id: String) -> Result<Vec<Chapter>> {
 			lang: String::from("en"),
 		}]
 	} else {
-		cache_api_request(&id)?;
-		let json = unsafe { CACHED_JSON.clone().unwrap() };
+		let json = cache_api_request(&parse_id(&id)?, false)?;
 
 		let scanlators_map = json.get("groups").as_object()?;
 		let chapters_object = json.get("chapters").as_object()?;
@@ -267,8 +303,7 @@ This source locally tracks and saves any series found, which can be disabled in
 		let id = split.next().unwrap_or_default();
 		let group = split.next().unwrap_or_default();
 
-		cache_api_request(&manga_id)?;
-		let json = unsafe { CACHED_JSON.clone().unwrap() };
+		let json = cache_api_request(&parse_id(&manga_id)?, false)?;
 		let chapters_object = json.get("chapters").as_object()?;
 
 		let chapter_object = chapters_object.get(id).as_object()?;
@@ -282,14 +317,12 @@ This source locally tracks and saves any series found, which can be disabled in
 			Kind::String => {
 				let endpoint = pages.as_string()?.read();
 				parse_page_array(
-					Request::new(&format!("https://cubari.moe{}", endpoint), HttpMethod::Get)
-						.json()
-						.as_array()?,
+					fetch_json(&format!("https://cubari.moe{}", endpoint))?
+						.as_array()
+						.map_err(|_| CubariError::InvalidJson)?,
 				)
 			}
-			_ => Err(AidokuError {
-				reason: AidokuErrorKind::Unimplemented,
-			}),
+			_ => Err(CubariError::InvalidJson.into()),
 		}
 	}
 }
@@ -301,8 +334,8 @@ fn modify_image_request(_: Request) {}
 fn handle_url(url: String) -> Result<DeepLink> {
 	// https://cubari.moe/read/imgur/hYhqG7b/
 	// ['imgur', 'hYhqG7b']
-	let slug = url_to_slug(url.clone());
-	let manga = Some(get_manga_details(slug.clone())?);
+	let slug = Slug::from_url(&url).ok_or(CubariError::InvalidSlug)?;
+	let manga = Some(get_manga_details(slug.to_string())?);
 	let chapter = if url.starts_with("https://cubari.moe/read/") {
 		let clone = url.clone();
 		let split = clone
@@ -311,8 +344,7 @@ fn handle_url(url: String) -> Result<DeepLink> {
 			.split('/')
 			.collect::<Vec<_>>();
 		if split.len() > 2 {
-			cache_api_request(&slug)?;
-			let json = unsafe { CACHED_JSON.clone().unwrap() };
+			let json = cache_api_request(&slug, false)?;
 			let scanlators_map = json.get("groups").as_object()?;
 			let chapters_object = json.get("chapters").as_object()?;
 
@@ -352,7 +384,21 @@ fn handle_url(url: String) -> Result<DeepLink> {
 
 #[handle_notification]
 fn handle_notification(notif: String) {
-	if notif == "deleteHistory" {
-		database::delete_all_manga().ok();
+	match notif.as_str() {
+		"deleteHistory" => {
+			// Cached responses would otherwise still show deleted series.
+			cache::clear();
+			match database::delete_all_manga() {
+				Ok(removed) => database::set_status(&format!("Deleted {removed} series")),
+				Err(_) => database::set_status("Couldn't delete the history"),
+			}
+		}
+		"clearCache" => cache::clear(),
+		"exportHistory" => transfer::export(),
+		"importHistory" => transfer::import(),
+		"remoteStorage.connect" => remotestorage::connect(),
+		"remoteStorage.sync" => remotestorage::sync_now(),
+		"remoteStorage.disconnect" => remotestorage::disconnect(),
+		_ => {}
 	}
 }
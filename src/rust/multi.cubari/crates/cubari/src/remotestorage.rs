@@ -1,54 +1,306 @@
 use aidoku::{
-    prelude::format,
-    error::Result,
-    std::{String, Vec, net::{Request, HttpMethod}, defaults::*},
-    Manga, StringRef
+	prelude::format,
+	std::{
+		current_date,
+		defaults::*,
+		net::{HttpMethod, Request},
+		ObjectRef, String, StringRef, Vec,
+	},
+	Manga,
 };
 use alloc::string::ToString;
-use crate::database;
 
+use crate::{
+	database,
+	error::CubariError,
+	fetch_json,
+	helper::{series_json, urlencode, Slug},
+};
+
+type Result<T> = core::result::Result<T, CubariError>;
+
+/// The module cubari.moe stores its history under.
+const MODULE: &str = "cubari";
+
+/// A client for a remoteStorage `cubari` module, laid out the same way as
+/// cubari.moe's own sync: one JSON object per series, stored at
+/// `series/{source}-{slug}`.
 pub struct RemoteStorage {
-    url: String,
-    token: String,
+	/// Address of the module root, e.g. `https://storage.example/user/cubari`.
+	url: String,
+	token: String,
 }
 
 impl RemoteStorage {
-    fn new<T: AsRef<str>>(url: T, token: T) -> Self {
-        Self {
-            url: url.as_ref().to_string(),
-            token: token.as_ref().to_string(),
-        }
-    }
-
-    fn get_all_series(&self) -> Result<Vec<Manga>> {
-        let json = Request::new(&self.url, HttpMethod::Get)
-            .header("Authorization", &format!("Bearer {}", self.token))
-            .json()
-            .as_object()?;
-        let items = json.get("items").as_object()?;
-        let series = items.get("series/").as_object()?;
-        let revision = series.get("ETag").as_string()?.read();
-        if defaults_get("history.revision").as_string().unwrap_or_else(|_| StringRef::from("")).read() == revision {
-            Ok(
-                database::series_list()
-                .unwrap_or_default()
-                .iter()
-                .filter_map(|series| database::get_manga(series).ok())
-                .collect::<Vec<_>>()
-            )
-        } else {
-            defaults_set("history.revision", StringRef::from(revision).0);
-            let json = Request::new(
-                &format!("{}/series/", self.url),
-                HttpMethod::Get
-            )
-            .header("Authorization", &format!("Bearer {}", self.token))
-            .json()
-            .as_object()?;
-            let items = json.get("items").as_object()?;
-        }
-        
-
-    }
+	pub fn new<T: AsRef<str>>(url: T, token: T) -> Self {
+		Self {
+			url: url.as_ref().trim_end_matches('/').to_string(),
+			token: token.as_ref().to_string(),
+		}
+	}
+
+	/// Builds a client from the stored storage URL and token, if both are set.
+	pub fn from_settings() -> Option<Self> {
+		let url = defaults_get("remoteStorage.url").as_string().ok()?.read();
+		let token = defaults_get("remoteStorage.token").as_string().ok()?.read();
+		if url.is_empty() || token.is_empty() {
+			None
+		} else {
+			Some(Self::new(url, token))
+		}
+	}
+
+	fn request(&self, path: &str, method: HttpMethod) -> Request {
+		Request::new(&format!("{}/{}", self.url, path), method)
+			.header("Authorization", &format!("Bearer {}", self.token))
+	}
+
+	/// Lists a folder, returning its `items` object of name to ETag and
+	/// content info.
+	fn get_folder(&self, path: &str) -> Result<ObjectRef> {
+		let request = self.request(path, HttpMethod::Get);
+		request.send();
+		check_status(request.status_code())?;
+		request
+			.json()
+			.as_object()
+			.and_then(|folder| folder.get("items").as_object())
+			.map_err(|_| CubariError::InvalidJson)
+	}
+
+	/// Pulls the remote history into the local database.
+	///
+	/// The ETag of the `series/` folder is kept in `history.revision`, so the
+	/// series are only fetched again once something changed remotely. The
+	/// revision isn't stored if any series couldn't be fetched, so they're
+	/// tried again on the next pull. Returns how many series were merged, and
+	/// always leaves a status saying how the pull went.
+	pub fn pull(&self) -> Result<usize> {
+		let root = self.get_folder("")?;
+		let revision = match root.get("series/").as_object() {
+			Ok(folder) => folder
+				.get("ETag")
+				.as_string()
+				.map_err(|_| CubariError::InvalidJson)?
+				.read(),
+			Err(_) => {
+				set_status("Nothing to sync yet");
+				return Ok(0);
+			}
+		};
+		if revision == stored_revision() {
+			set_status(&format!("Up to date at revision {revision}"));
+			return Ok(0);
+		}
+
+		let items = self.get_folder("series/")?;
+		let mut merged = 0;
+		let mut incomplete = false;
+		for name in items.keys() {
+			// A malformed entry is skipped, so one bad document doesn't stop
+			// the rest from syncing.
+			let name = match name.as_string() {
+				Ok(name) => name.read(),
+				Err(_) => continue,
+			};
+			if name.is_empty() || name.ends_with('/') {
+				continue;
+			}
+			let request = self.request(&format!("series/{name}"), HttpMethod::Get);
+			request.send();
+			if CubariError::from_status(request.status_code()).is_some() {
+				// Keep the old revision, so the next pull fetches it again.
+				incomplete = true;
+				continue;
+			}
+			let Ok(series) = request.json().as_object() else {
+				continue;
+			};
+			let string = |key: &str| {
+				series
+					.get(key)
+					.as_string()
+					.map(|s| s.read())
+					.unwrap_or_default()
+			};
+			let Some(slug) = Slug::parse(format!("{}/{}", string("source"), string("slug"))) else {
+				continue;
+			};
+			let merge = database::merge_manga(
+				&slug.to_string(),
+				&string("title"),
+				&string("coverUrl"),
+				// cubari.moe stores when the series was last read, in milliseconds.
+				series.get("timestamp").as_float().unwrap_or(0.0) / 1000.0,
+			);
+			if merge.is_ok() {
+				merged += 1;
+			}
+		}
+		if incomplete {
+			set_status("Some series couldn't be fetched, sync again to retry");
+		} else {
+			defaults_set("history.revision", StringRef::from(&revision).0);
+			set_status(&format!("Synced revision {revision}"));
+		}
+		Ok(merged)
+	}
+
+	/// Uploads the local series the remote doesn't have yet, then pulls the
+	/// remote history. Returns how many series were merged.
+	pub fn sync(&self) -> Result<usize> {
+		let remote = match self.get_folder("series/") {
+			Ok(items) => items
+				.keys()
+				.filter_map(|name| name.as_string().ok().map(|name| name.read()))
+				.collect::<Vec<_>>(),
+			// The folder doesn't exist until the first series is stored.
+			Err(_) => Vec::new(),
+		};
+		for id in database::series_list().unwrap_or_default() {
+			if remote.contains(&id.replacen('/', "-", 1)) {
+				continue;
+			}
+			if let Ok(manga) = database::get_manga(&id) {
+				self.push(&manga)?;
+			}
+		}
+		self.pull()
+	}
+
+	/// Uploads a series to the remote history. Series that are already
+	/// stored remotely are left alone, since they carry the read progress
+	/// from cubari.moe.
+	pub fn push(&self, manga: &Manga) -> Result<()> {
+		let slug = Slug::parse(&manga.id).ok_or(CubariError::InvalidSlug)?;
+		let body = series_json(&slug, manga, current_date());
+		let request = self
+			.request(
+				&format!("series/{}-{}", slug.source, slug.id),
+				HttpMethod::Put,
+			)
+			.header("Content-Type", "application/json")
+			.header("If-None-Match", "*")
+			.body(body.as_bytes());
+		request.send();
+		match request.status_code() {
+			// 412 means the series already exists remotely.
+			412 => Ok(()),
+			status => check_status(status),
+		}
+	}
+
+	/// Removes a series from the remote history, so the next pull doesn't
+	/// bring it back.
+	pub fn remove(&self, slug: &Slug) -> Result<()> {
+		let request = self.request(
+			&format!("series/{}-{}", slug.source, slug.id),
+			HttpMethod::Delete,
+		);
+		request.send();
+		match request.status_code() {
+			// 404 means it was never synced.
+			404 => Ok(()),
+			status => check_status(status),
+		}
+	}
 }
 
+fn check_status(status: i32) -> Result<()> {
+	match CubariError::from_status(status) {
+		Some(error) => Err(error),
+		None => Ok(()),
+	}
+}
+
+fn stored_revision() -> String {
+	defaults_get("history.revision")
+		.as_string()
+		.map(|s| s.read())
+		.unwrap_or_default()
+}
+
+fn set_status(status: &str) {
+	defaults_set("remoteStorage.status", StringRef::from(status).0);
+}
+
+/// Looks up the storage root for a `user@host` address with WebFinger.
+fn discover(address: &str) -> Result<String> {
+	let host = address.rsplit('@').next().unwrap_or_default();
+	let links = fetch_json(&format!(
+		"https://{host}/.well-known/webfinger?resource={}",
+		urlencode(format!("acct:{address}"))
+	))?
+	.as_object()
+	.and_then(|json| json.get("links").as_array())
+	.map_err(|_| CubariError::InvalidJson)?;
+	for link in links {
+		let Ok(link) = link.as_object() else {
+			continue;
+		};
+		let rel = link
+			.get("rel")
+			.as_string()
+			.map(|s| s.read())
+			.unwrap_or_default();
+		if rel.contains("remotestorage") {
+			return link
+				.get("href")
+				.as_string()
+				.map(|s| s.read())
+				.map_err(|_| CubariError::InvalidJson);
+		}
+	}
+	Err(CubariError::NotFound)
+}
+
+/// Resolves the address entered in settings to the storage URL of the
+/// Cubari module. The address can also be the storage root itself.
+pub fn connect() {
+	let address = defaults_get("remoteStorage.address")
+		.as_string()
+		.map(|s| s.read())
+		.unwrap_or_default();
+	let address = address.trim();
+	let root = if address.starts_with("http://") || address.starts_with("https://") {
+		Ok(String::from(address))
+	} else if address.contains('@') {
+		discover(address)
+	} else {
+		set_status("Enter a user address or storage URL");
+		return;
+	};
+	match root {
+		Ok(root) => {
+			let url = format!("{}/{MODULE}", root.trim_end_matches('/'));
+			defaults_set("remoteStorage.url", StringRef::from(&url).0);
+			defaults_set("history.revision", StringRef::from("").0);
+			set_status(&format!("Connected to {url}"));
+		}
+		Err(CubariError::NotFound) => set_status("No remoteStorage found for this address"),
+		Err(error) => set_status(&format!("Couldn't look up this address: {error}")),
+	}
+}
+
+pub fn sync_now() {
+	match RemoteStorage::from_settings() {
+		Some(remote) => {
+			// A successful sync writes its own status.
+			if let Err(error) = remote.sync() {
+				set_status(&format!("Sync failed: {error}"));
+			}
+		}
+		None => set_status("Connect and enter a token first"),
+	}
+}
+
+pub fn disconnect() {
+	for key in [
+		"remoteStorage.url",
+		"remoteStorage.token",
+		"history.revision",
+	] {
+		defaults_set(key, StringRef::from("").0);
+	}
+	set_status("Not connected");
+}
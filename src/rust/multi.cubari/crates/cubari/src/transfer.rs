@@ -0,0 +1,122 @@
+use aidoku::{
+	error::Result,
+	prelude::format,
+	std::{
+		defaults::*,
+		json,
+		net::{HttpMethod, Request},
+		Kind, String, StringRef, ValueRef, Vec,
+	},
+};
+use alloc::string::ToString;
+
+use crate::{
+	database,
+	helper::{series_json, Slug},
+};
+
+/// Text field the exported history is written to.
+const EXPORT_KEY: &str = "history.export";
+
+/// Text field holding the JSON or URL to import.
+const IMPORT_KEY: &str = "history.import";
+
+/// Writes the history to the export field as a JSON array of series in
+/// cubari.moe's sync format, ready to be shared as a gist.
+pub fn export() {
+	let items = database::series_list()
+		.unwrap_or_default()
+		.iter()
+		.filter_map(|id| {
+			let slug = Slug::parse(id)?;
+			let entry = database::get_entry(id).ok()?;
+			Some(series_json(&slug, &entry.manga, entry.last_seen))
+		})
+		.collect::<Vec<_>>();
+	defaults_set(
+		EXPORT_KEY,
+		StringRef::from(format!("[{}]", items.join(","))).0,
+	);
+	database::set_status(&format!("Exported {} series", items.len()));
+}
+
+/// Links to a gist page are turned into a link to its raw content.
+fn raw_url(url: &str) -> String {
+	if url.contains("gist.github.com/") && !url.contains("/raw") {
+		format!(
+			"{}/raw",
+			url.replace("gist.github.com", "gist.githubusercontent.com")
+				.trim_end_matches('/')
+		)
+	} else {
+		String::from(url)
+	}
+}
+
+fn read_import() -> Result<ValueRef> {
+	let input = defaults_get(IMPORT_KEY).as_string()?.read();
+	let input = input.trim();
+	if input.starts_with("http://") || input.starts_with("https://") {
+		Ok(Request::new(&raw_url(input), HttpMethod::Get).json())
+	} else {
+		Ok(json::parse(input.as_bytes()))
+	}
+}
+
+/// A series to import: its slug, title, cover and when it was last read.
+fn parse_item(item: ValueRef) -> Option<(Slug, String, String, f64)> {
+	match item.kind() {
+		// A plain list of slugs or URLs.
+		Kind::String => Some((
+			Slug::from_url(item.as_string().ok()?.read())?,
+			String::new(),
+			String::new(),
+			0.0,
+		)),
+		Kind::Object => {
+			let obj = item.as_object().ok()?;
+			let string = |key: &str| {
+				obj.get(key)
+					.as_string()
+					.map(|s| s.read())
+					.unwrap_or_default()
+			};
+			let slug = Slug::parse(format!("{}/{}", string("source"), string("slug")))
+				.or_else(|| Slug::from_url(string("url")))?;
+			Some((
+				slug,
+				string("title"),
+				string("coverUrl"),
+				obj.get("timestamp").as_float().unwrap_or(0.0) / 1000.0,
+			))
+		}
+		_ => None,
+	}
+}
+
+/// Adds the series from the pasted JSON, or the JSON at the pasted URL, to
+/// the history. Takes the format written by [`export`], or a list of slugs
+/// or URLs.
+pub fn import() {
+	let items = match read_import().and_then(|value| value.as_array()) {
+		Ok(items) => items,
+		Err(_) => {
+			database::set_status("Couldn't read a list of series to import");
+			return;
+		}
+	};
+	let mut imported = 0;
+	for item in items {
+		let Some((slug, title, cover, last_seen)) = parse_item(item) else {
+			continue;
+		};
+		let id = slug.to_string();
+		// Untitled series get their real title once they're opened.
+		let title = if title.is_empty() { id.clone() } else { title };
+		if database::merge_manga(&id, &title, &cover, last_seen).is_ok() {
+			imported += 1;
+		}
+	}
+	defaults_set(IMPORT_KEY, StringRef::from("").0);
+	database::set_status(&format!("Imported {imported} series"));
+}
@@ -2,6 +2,7 @@
 
 extern crate alloc;
 use alloc::vec;
+use core::fmt;
 
 #[cfg(test)]
 use alloc::{format, string::String, vec::Vec};
@@ -34,6 +35,12 @@ fn base64_encode<T: AsRef<[u8]>>(str: T) -> String {
 /// Returns the original URL if not parsable.
 pub fn url_to_slug<T: AsRef<str>>(url: T) -> String {
 	let url = url.as_ref();
+	parse_url(url).unwrap_or_else(|| String::from(url))
+}
+
+/// Does the work for [`url_to_slug`]. Returns `None` for URLs of a known
+/// site that are missing the part the slug is taken from.
+fn parse_url(url: &str) -> Option<String> {
 	let slash_count = url.matches('/').count();
 	let query = url
 		.trim_start_matches("http")
@@ -51,10 +58,10 @@ pub fn url_to_slug<T: AsRef<str>>(url: T) -> String {
 		// where slug is always the last part of the URL.
 		let domain = query.split('/').next().unwrap_or_default();
 		let source = domain.split('.').nth_back(1).unwrap_or_default();
-		let slug = query.split('/').last().unwrap_or_default();
-		format!("{source}/{slug}")
+		let slug = query.split('/').next_back().unwrap_or_default();
+		Some(format!("{source}/{slug}"))
 	} else if query.contains("git.io") {
-		format!("gist/{}", query.trim_start_matches("git.io/"))
+		Some(format!("gist/{}", query.trim_start_matches("git.io/")))
 	} else if query.contains("gist.githubusercontent.com/")
 		|| query.contains("gist.github.com/") && query.contains("raw")
 	{
@@ -64,25 +71,25 @@ pub fn url_to_slug<T: AsRef<str>>(url: T) -> String {
 				.trim_start_matches("gist.githubusercontent.com/")
 				.trim_start_matches("gist.github.com/"),
 		);
-		format!("gist/{}", base64_encode(temp))
+		Some(format!("gist/{}", base64_encode(temp)))
 	} else if query.contains("mangasee123.com/manga") || query.contains("manga4life.com/manga") {
-		format!(
+		Some(format!(
 			"mangasee/{}",
 			query
 				.trim_start_matches("manga")
 				.trim_start_matches("see123")
 				.trim_start_matches("4life")
 				.trim_start_matches(".com/manga/")
-		)
+		))
 	} else if query.contains("mangadex.org/title") {
-		let split = query.split('/').collect::<Vec<_>>();
-		format!("mangadex/{}", split[2])
+		let id = query.split('/').nth(2)?;
+		Some(format!("mangadex/{id}"))
 	} else if query.contains("mangakatana") {
 		// Generic parser for anything that has the entire URL base64-encoded as a slug.
 		let domain = query.split('/').next().unwrap_or_default();
 		let source = domain.split('.').next().unwrap_or_default();
 
-		format!("{source}/{}", base64_encode(url))
+		Some(format!("{source}/{}", base64_encode(url)))
 	} else if (query.contains("assortedscans.com") || query.contains("arc-relight.com"))
 		&& slash_count >= 4
 	{
@@ -90,27 +97,74 @@ pub fn url_to_slug<T: AsRef<str>>(url: T) -> String {
 		let split = url.split('/').collect::<Vec<_>>();
 		let slug = format!(
 			"{}/{}/{}",
-			split[0].trim_end_matches(':'),
-			split[2],
-			split[4]
+			split.first()?.trim_end_matches(':'),
+			split.get(2)?,
+			split.get(4)?
 		);
 
-		format!("mangadventure/{}", base64_encode(slug))
+		Some(format!("mangadventure/{}", base64_encode(slug)))
 	} else if query.contains("cubari.moe/read") && slash_count >= 3 {
-		let split = query
+		let mut split = query
 			.trim_start_matches("cubari.moe/read/")
 			.trim_end_matches('/')
-			.split('/')
-			.collect::<Vec<_>>();
-		format!("{}/{}", split[0], split[1])
+			.split('/');
+		let source = split.next().filter(|s| !s.is_empty())?;
+		let slug = split.next().filter(|s| !s.is_empty())?;
+		Some(format!("{source}/{slug}"))
 	} else {
-		String::from(url)
+		None
+	}
+}
+
+/// A series on Cubari, identified by the source it's proxied from and its
+/// slug on that source, e.g. `imgur/hYhqG7b`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Slug {
+	pub source: String,
+	pub id: String,
+}
+
+impl Slug {
+	/// Parses a `{source}/{id}` slug, ignoring a trailing slash.
+	///
+	/// # Returns
+	/// Returns `None` if either part is missing, there are more than two parts,
+	/// or a part contains characters that can't appear in a Cubari URL.
+	pub fn parse<T: AsRef<str>>(slug: T) -> Option<Self> {
+		let (source, id) = slug.as_ref().trim().trim_end_matches('/').split_once('/')?;
+		let valid_source = !source.is_empty()
+			&& source
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+		let valid_id = !id.is_empty()
+			&& id
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || "-_.~=%+".contains(c));
+		if valid_source && valid_id {
+			Some(Self {
+				source: String::from(source),
+				id: String::from(id),
+			})
+		} else {
+			None
+		}
+	}
+
+	/// Parses a compatible URL (see [`url_to_slug`]) or a slug.
+	pub fn from_url<T: AsRef<str>>(url: T) -> Option<Self> {
+		Self::parse(url_to_slug(url))
+	}
+}
+
+impl fmt::Display for Slug {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}/{}", self.source, self.id)
 	}
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::url_to_slug;
+	use crate::{url_to_slug, Slug};
 
 	macro_rules! generate_test {
 		($url:expr, $matches:expr) => {
@@ -217,4 +271,44 @@ mod tests {
 		);
 		assert_eq!(url_to_slug("nhentai/177013"), "nhentai/177013");
 	}
+
+	#[test]
+	fn test_slug_parse() {
+		let slug = Slug::parse("imgur/hYhqG7b").unwrap();
+		assert_eq!(slug.source, "imgur");
+		assert_eq!(slug.id, "hYhqG7b");
+		assert_eq!(slug.to_string(), "imgur/hYhqG7b");
+		assert_eq!(
+			Slug::parse("nhentai/177013/"),
+			Slug::parse("nhentai/177013")
+		);
+	}
+
+	#[test]
+	fn test_slug_rejects_malformed() {
+		assert_eq!(Slug::parse(""), None);
+		assert_eq!(Slug::parse("imgur"), None);
+		assert_eq!(Slug::parse("imgur/"), None);
+		assert_eq!(Slug::parse("/hYhqG7b"), None);
+		assert_eq!(Slug::parse("imgur/hYhqG7b/1"), None);
+		assert_eq!(Slug::parse("some title/with spaces"), None);
+		assert_eq!(Slug::from_url("https://www.google.com"), None);
+		assert_eq!(Slug::from_url("https://cubari.moe/read/imgur/"), None);
+		assert_eq!(Slug::from_url("https://mangadex.org/title"), None);
+	}
+
+	#[test]
+	fn test_slug_from_url() {
+		assert_eq!(
+			Slug::from_url("https://cubari.moe/read/nhentai/408179/1/"),
+			Some(Slug {
+				source: "nhentai".to_owned(),
+				id: "408179".to_owned(),
+			})
+		);
+		assert_eq!(
+			Slug::from_url("https://imgur.com/a/hYhqG7b").map(|s| s.to_string()),
+			Some("imgur/hYhqG7b".to_owned())
+		);
+	}
 }